@@ -4,18 +4,89 @@ use crate::events::*;
 use crate::game_logic::*;
 use crate::socket::SendToServerEvent;
 use crate::socket::SocketMessageSender;
+use crate::ui::GameScore;
 use crate::ui::RootUINode;
 use bevy::prelude::*;
 use bevy::transform::commands;
+use connect_four_lib::config::{GameConfig, Rules};
 
-pub const BOARD_WIDTH: f32 = CELL_SIZE * 7.0;
-pub const BOARD_HEIGHT: f32 = CELL_SIZE * 6.0;
+// Space the board can take up, cells shrink to fit bigger boards
+pub const MAX_BOARD_WIDTH: f32 = 760.0;
+pub const MAX_BOARD_HEIGHT: f32 = 440.0;
+// Size of a cell and piece on the standard board
 pub const CELL_SIZE: f32 = 62.0;
 pub const PIECE_RADIUS: f32 = 24.0;
 pub const BOARD_COLOR: Color = Color::srgb(0.2, 0.4, 0.8);
 pub const HOLE_COLOR: Color = Color::srgb(0.1, 0.2, 0.4);
+pub const BLOCKER_COLOR: Color = Color::srgb(0.45, 0.45, 0.5);
 pub const BOARD_OFFSET_Y: f32 = -60.0; // Offset to position board below UI
 
+// Opacity of the copies of the edge columns drawn beside a cylinder board
+pub const GHOST_ALPHA: f32 = 0.35;
+
+// Where everything on the board is drawn for a given board size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardLayout {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    pub piece_radius: f32,
+    pub width: f32,
+    pub height: f32,
+    // Cylinder boards get a faded copy of the opposite edge column on each side
+    pub wrap_around: bool,
+}
+
+impl BoardLayout {
+    pub fn new(config: GameConfig) -> Self {
+        let wrap_around = config.rules == Rules::Cylinder;
+        let drawn_columns = config.width + if wrap_around { 2 } else { 0 };
+        let cell_size = CELL_SIZE
+            .min(MAX_BOARD_WIDTH / drawn_columns as f32)
+            .min(MAX_BOARD_HEIGHT / config.height as f32);
+        Self {
+            columns: config.width,
+            rows: config.height,
+            cell_size,
+            piece_radius: PIECE_RADIUS * cell_size / CELL_SIZE,
+            width: cell_size * config.width as f32,
+            height: cell_size * config.height as f32,
+            wrap_around,
+        }
+    }
+
+    // The column copied into each ghost column and where the copy is drawn: the last column
+    // to the left of the board and the first column to the right
+    pub fn ghost_columns(&self) -> Vec<(usize, f32)> {
+        if !self.wrap_around {
+            return Vec::new();
+        }
+        vec![
+            (self.columns - 1, self.column_x(0) - self.cell_size),
+            (0, self.column_x(self.columns - 1) + self.cell_size),
+        ]
+    }
+
+    // Center of the cells in `col`
+    pub fn column_x(&self, col: usize) -> f32 {
+        -(self.width / 2.0) + (self.cell_size / 2.0) + (col as f32 * self.cell_size)
+    }
+
+    // Center of the cells in `row`
+    pub fn row_y(&self, row: usize) -> f32 {
+        (self.height / 2.0) - (self.cell_size / 2.0) + BOARD_OFFSET_Y
+            - (row as f32 * self.cell_size)
+    }
+
+    pub fn piece_size(&self) -> Vec2 {
+        Vec2::splat(self.piece_radius * 2.0)
+    }
+}
+
+// Everything drawn for the empty board, rebuilt when the board size changes
+#[derive(Component)]
+pub struct BoardSprite;
+
 #[derive(Component)]
 pub struct BoardCell {
     pub row: usize,
@@ -29,6 +100,14 @@ pub struct GamePiece {
     pub player: Player,
 }
 
+// Faded copy of a piece in an edge column, redrawn whenever the board changes
+#[derive(Component)]
+pub struct GhostPiece;
+
+// Neutral blocker cell, redrawn whenever the board changes
+#[derive(Component)]
+pub struct BlockerSprite;
+
 #[derive(Component)]
 pub struct AnimatingPiece {
     pub target_row: usize,
@@ -48,34 +127,141 @@ pub struct PreviewPiece {
     pub col: usize,
 }
 
-pub fn setup_board(mut commands: Commands) {
+pub fn setup_board(mut commands: Commands, game_state: Res<GameState>) {
+    spawn_board(&mut commands, BoardLayout::new(game_state.config));
+}
+
+type DrawnOnBoard = Or<(
+    With<BoardSprite>,
+    With<GamePiece>,
+    With<AnimatingPiece>,
+    With<GhostPiece>,
+    With<BlockerSprite>,
+)>;
+
+// Redraws the board when the server sends a different board size
+pub fn resize_board(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    mut drawn: Local<Option<GameConfig>>,
+    board: Query<Entity, DrawnOnBoard>,
+) {
+    let config = game_state.config;
+    let Some(drawn_config) = *drawn else {
+        // Drawn by `setup_board`
+        *drawn = Some(config);
+        return;
+    };
+    if drawn_config == config {
+        return;
+    }
+    info!("resizing board to {}x{}", config.width, config.height);
+    for entity in board.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_board(&mut commands, BoardLayout::new(config));
+    *drawn = Some(config);
+}
+
+// Copies the pieces in the edge columns of a cylinder board into the ghost columns
+pub fn update_ghost_pieces(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    ghosts: Query<Entity, With<GhostPiece>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn();
+    }
+    let layout = BoardLayout::new(game_state.config);
+    for (col, x) in layout.ghost_columns() {
+        for row in 0..layout.rows {
+            let color = match game_state.get_cell(row, col) {
+                Cell::Piece(player) => player.color(),
+                Cell::Blocker => Some(BLOCKER_COLOR),
+                Cell::Empty => None,
+            };
+            let Some(color) = color else {
+                continue;
+            };
+            commands.spawn((
+                Sprite {
+                    color: color.with_alpha(GHOST_ALPHA),
+                    custom_size: Some(layout.piece_size()),
+                    ..default()
+                },
+                Transform {
+                    translation: Vec3::new(x, layout.row_y(row), 2.0),
+                    ..default()
+                },
+                GhostPiece,
+            ));
+        }
+    }
+}
+
+// Draws the blockers as grey squares, so they can't be mistaken for pieces
+pub fn update_blockers(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    blockers: Query<Entity, With<BlockerSprite>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    for entity in blockers.iter() {
+        commands.entity(entity).despawn();
+    }
+    let layout = BoardLayout::new(game_state.config);
+    for row in 0..layout.rows {
+        for col in 0..layout.columns {
+            if game_state.get_cell(row, col) != Cell::Blocker {
+                continue;
+            }
+            commands.spawn((
+                Sprite {
+                    color: BLOCKER_COLOR,
+                    custom_size: Some(Vec2::splat(layout.cell_size * 0.9)),
+                    ..default()
+                },
+                Transform {
+                    translation: Vec3::new(layout.column_x(col), layout.row_y(row), 2.0),
+                    ..default()
+                },
+                BlockerSprite,
+            ));
+        }
+    }
+}
+
+fn spawn_board(commands: &mut Commands, layout: BoardLayout) {
     // Spawn the board background
     commands.spawn((
         Sprite {
             color: BOARD_COLOR,
-            custom_size: Some(Vec2::new(BOARD_WIDTH, BOARD_HEIGHT)),
+            custom_size: Some(Vec2::new(layout.width, layout.height)),
             ..default()
         },
         Transform {
             translation: Vec3::new(0.0, BOARD_OFFSET_Y, 0.0),
             ..default()
         },
+        BoardSprite,
     ));
 
     // Create the grid of holes
-    let start_x = -(BOARD_WIDTH / 2.0) + (CELL_SIZE / 2.0);
-    let start_y = (BOARD_HEIGHT / 2.0) - (CELL_SIZE / 2.0) + BOARD_OFFSET_Y;
-
-    for row in 0..6 {
-        for col in 0..7 {
-            let x = start_x + (col as f32 * CELL_SIZE);
-            let y = start_y - (row as f32 * CELL_SIZE);
+    for row in 0..layout.rows {
+        for col in 0..layout.columns {
+            let x = layout.column_x(col);
+            let y = layout.row_y(row);
 
             // Create the hole (visual representation of empty cell)
             commands.spawn((
                 Sprite {
                     color: HOLE_COLOR,
-                    custom_size: Some(Vec2::new(PIECE_RADIUS * 2.0, PIECE_RADIUS * 2.0)),
+                    custom_size: Some(layout.piece_size()),
                     ..default()
                 },
                 Transform {
@@ -83,18 +269,49 @@ pub fn setup_board(mut commands: Commands) {
                     ..default()
                 },
                 BoardCell { row, col },
+                BoardSprite,
+            ));
+        }
+    }
+
+    // Faded edge columns showing how the sides join up
+    for (_, x) in layout.ghost_columns() {
+        commands.spawn((
+            Sprite {
+                color: BOARD_COLOR.with_alpha(GHOST_ALPHA),
+                custom_size: Some(Vec2::new(layout.cell_size, layout.height)),
+                ..default()
+            },
+            Transform {
+                translation: Vec3::new(x, BOARD_OFFSET_Y, 0.0),
+                ..default()
+            },
+            BoardSprite,
+        ));
+        for row in 0..layout.rows {
+            commands.spawn((
+                Sprite {
+                    color: HOLE_COLOR.with_alpha(GHOST_ALPHA),
+                    custom_size: Some(layout.piece_size()),
+                    ..default()
+                },
+                Transform {
+                    translation: Vec3::new(x, layout.row_y(row), 1.0),
+                    ..default()
+                },
+                BoardSprite,
             ));
         }
     }
 
     // Create column highlights (invisible by default)
-    for col in 0..7 {
-        let x = start_x + (col as f32 * CELL_SIZE);
+    for col in 0..layout.columns {
+        let x = layout.column_x(col);
 
         commands.spawn((
             Sprite {
                 color: Color::srgba(1.0, 1.0, 1.0, 0.0), // Transparent by default
-                custom_size: Some(Vec2::new(CELL_SIZE, BOARD_HEIGHT)),
+                custom_size: Some(Vec2::new(layout.cell_size, layout.height)),
                 ..default()
             },
             Transform {
@@ -103,22 +320,24 @@ pub fn setup_board(mut commands: Commands) {
             },
             Visibility::Hidden,
             ColumnHighlight { col },
+            BoardSprite,
         ));
 
         // Hovering preview piece for this column
         commands.spawn((
             Sprite {
                 color: Color::srgba(0.8, 0.2, 0.2, 0.7), // Preview piece color (red, semi-transparent)
-                custom_size: Some(Vec2::new(PIECE_RADIUS * 2.0, PIECE_RADIUS * 2.0)),
+                custom_size: Some(layout.piece_size()),
                 ..default()
             },
             Transform {
                 // Place slightly above the top row
-                translation: Vec3::new(x, BOARD_OFFSET_Y + BOARD_HEIGHT / 2. + CELL_SIZE / 2., 3.0),
+                translation: Vec3::new(x, layout.row_y(0) + layout.cell_size, 3.0),
                 ..default()
             },
             Visibility::Hidden,
             PreviewPiece { col },
+            BoardSprite,
         ));
     }
 }
@@ -142,12 +361,12 @@ pub fn handle_input(
     my_player: Res<crate::MyPlayerInfo>,
 ) {
     // Only allow move if I'm the active player
-    let is_my_turn = match my_player.color {
-        Some(Player::One) => game_state.current_player == Player::One,
-        Some(Player::Two) => game_state.current_player == Player::Two,
-        _ => false,
-    };
+    let is_my_turn = my_player
+        .color
+        .is_some_and(|color| color != Player::Spectator && color == game_state.current_player);
 
+    let layout = BoardLayout::new(game_state.config);
+    let columns = 0..layout.columns as i32;
     let _window = windows.single();
     if let Ok((_camera, camera_transform)) = camera.single() {
         let window = match windows.single() {
@@ -163,23 +382,22 @@ pub fn handle_input(
                     0.0,
                 ))
                 .truncate();
-            let start_x = -(BOARD_WIDTH / 2.0);
-            // let start_y = (BOARD_HEIGHT / 2.0) + BOARD_OFFSET_Y;
-            // let board_bottom = (BOARD_HEIGHT / 2.0) + BOARD_OFFSET_Y - BOARD_HEIGHT;
-            // let board_top = (BOARD_HEIGHT / 2.0) + BOARD_OFFSET_Y - 2.0 * CELL_SIZE;
+            let (board_width, board_height, cell_size) =
+                (layout.width, layout.height, layout.cell_size);
+            let start_x = -(board_width / 2.0);
             if world_pos.x >= start_x
-                && world_pos.x <= start_x + BOARD_WIDTH
+                && world_pos.x <= start_x + board_width
                 && world_pos.y
-                    >= ((BOARD_OFFSET_Y - BOARD_HEIGHT + (2.0 * CELL_SIZE) + (CELL_SIZE / 2.0))
+                    >= ((BOARD_OFFSET_Y - board_height + (2.0 * cell_size) + (cell_size / 2.0))
                         / 2.0)
                 && world_pos.y
-                    <= ((BOARD_OFFSET_Y + BOARD_HEIGHT + (2.0 * CELL_SIZE) + (CELL_SIZE / 2.0))
+                    <= ((BOARD_OFFSET_Y + board_height + (2.0 * cell_size) + (cell_size / 2.0))
                         / 2.0)
             {
-                let col = (((world_pos.x - start_x) / CELL_SIZE).floor()) as i32;
+                let col = (((world_pos.x - start_x) / cell_size).floor()) as i32;
                 // Update column highlights
                 for (mut sprite, mut visibility, highlight) in param_set.p0().iter_mut() {
-                    if (0..7).contains(&col) && highlight.col == col as usize {
+                    if columns.contains(&col) && highlight.col == col as usize {
                         if game_state.status == GameStatus::Playing
                             && !game_state.is_column_full(col as usize)
                         {
@@ -195,7 +413,7 @@ pub fn handle_input(
 
                 // Update hovering preview piece color/visibility
                 for (mut sprite, mut visibility, preview) in param_set.p1().iter_mut() {
-                    if (0..7).contains(&col) && preview.col == col as usize {
+                    if columns.contains(&col) && preview.col == col as usize {
                         if game_state.status == GameStatus::Playing
                             && !game_state.is_column_full(col as usize)
                         {
@@ -215,7 +433,7 @@ pub fn handle_input(
 
                 // Handle mouse clicks
                 if mouse_input.just_pressed(MouseButton::Left)
-                    && (0..7).contains(&col)
+                    && columns.contains(&col)
                     && is_my_turn
                 {
                     let col = col as usize;
@@ -231,6 +449,36 @@ pub fn handle_input(
                         }));
                     }
                 }
+            } else if matches!(game_state.config.rules, Rules::PopOut | Rules::Pop10)
+                && world_pos.x >= start_x
+                && world_pos.x <= start_x + board_width
+                // `world_pos.y` grows downwards, so this is the strip just below the board
+                && world_pos.y >= board_height / 2.0 - BOARD_OFFSET_Y
+                && world_pos.y <= board_height / 2.0 - BOARD_OFFSET_Y + cell_size
+            {
+                let col = (((world_pos.x - start_x) / cell_size).floor()) as usize;
+                let can_pop = game_state.status == GameStatus::Playing
+                    && is_my_turn
+                    && game_state.get_piece(layout.rows - 1, col) == my_player.color;
+                // Highlight the column being popped from, no piece is dropped
+                for (mut sprite, mut visibility, highlight) in param_set.p0().iter_mut() {
+                    if can_pop && highlight.col == col {
+                        *visibility = Visibility::Visible;
+                        sprite.color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+                    } else {
+                        *visibility = Visibility::Hidden;
+                    }
+                }
+                for (_sprite, mut visibility, _preview) in param_set.p1().iter_mut() {
+                    *visibility = Visibility::Hidden;
+                }
+
+                if mouse_input.just_pressed(MouseButton::Left) && can_pop {
+                    if let Some(id) = my_player.id.clone() {
+                        info!("sending message to server to pop col: {col}");
+                        sender.write(SendToServerEvent(WsMsg::ClientPop { id, col }));
+                    }
+                }
             } else {
                 // Hide all highlights and previews when not on board
                 for (_sprite, mut visibility, _highlight) in param_set.p0().iter_mut() {
@@ -246,8 +494,10 @@ pub fn handle_input(
 
 pub fn handle_piece_drop(
     mut commands: Commands,
+    game_state: Res<GameState>,
     mut piece_drop_events: EventReader<PieceDropEvent>,
 ) {
+    let layout = BoardLayout::new(game_state.config);
     for event in piece_drop_events.read() {
         // Always use the game state's current player for piece color (matches latest move)
         let piece_color = event.player;
@@ -255,18 +505,15 @@ pub fn handle_piece_drop(
         let target_row = event.row;
 
         // Calculate positions for animation
-        let start_x = -(BOARD_WIDTH / 2.0) + (CELL_SIZE / 2.0);
-        let start_y = (BOARD_HEIGHT / 2.0) - (CELL_SIZE / 2.0) + BOARD_OFFSET_Y;
-
-        let x = start_x + (event.column as f32 * CELL_SIZE);
-        let start_y_pos = start_y + CELL_SIZE; // Start above the board
-        let target_y_pos = start_y - (target_row as f32 * CELL_SIZE);
+        let x = layout.column_x(event.column);
+        let start_y_pos = layout.row_y(0) + layout.cell_size; // Start above the board
+        let target_y_pos = layout.row_y(target_row);
 
         // Spawn the animated piece using the state player
         commands.spawn((
             Sprite {
                 color: piece_color.color().expect("could not get color"),
-                custom_size: Some(Vec2::new(PIECE_RADIUS * 2.0, PIECE_RADIUS * 2.0)),
+                custom_size: Some(layout.piece_size()),
                 ..default()
             },
             Transform {
@@ -284,6 +531,78 @@ pub fn handle_piece_drop(
     }
 }
 
+// Fog of war: removes pieces that are no longer in view and drops in the ones that came
+// into view
+pub fn sync_pieces(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    mut board_sync_events: EventReader<BoardSyncEvent>,
+    pieces: Query<(Entity, &GamePiece)>,
+    animating_pieces: Query<&AnimatingPiece>,
+    mut piece_drop_events: EventWriter<PieceDropEvent>,
+) {
+    if board_sync_events.read().count() == 0 {
+        return;
+    }
+    let mut drawn = Vec::new();
+    for (entity, piece) in pieces.iter() {
+        if game_state.get_piece(piece.row, piece.col) == Some(piece.player) {
+            drawn.push((piece.row, piece.col));
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    drawn.extend(
+        animating_pieces
+            .iter()
+            .map(|anim| (anim.target_row, anim.col)),
+    );
+    for (row, cells) in game_state.board.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            if let Cell::Piece(player) = cell {
+                if !drawn.contains(&(row, col)) {
+                    piece_drop_events.write(PieceDropEvent {
+                        column: col,
+                        row,
+                        player: *player,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Removes the popped piece and lets the rest of the column fall a row
+pub fn handle_piece_pop(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    mut piece_pop_events: EventReader<PiecePopEvent>,
+    pieces: Query<(Entity, &Transform, &GamePiece)>,
+) {
+    let layout = BoardLayout::new(game_state.config);
+    for event in piece_pop_events.read() {
+        for (entity, transform, piece) in pieces.iter() {
+            if piece.col != event.column {
+                continue;
+            }
+            if piece.row == layout.rows - 1 {
+                commands.entity(entity).despawn();
+            } else {
+                commands
+                    .entity(entity)
+                    .remove::<GamePiece>()
+                    .insert(AnimatingPiece {
+                        target_row: piece.row + 1,
+                        col: piece.col,
+                        start_y: transform.translation.y,
+                        target_y: layout.row_y(piece.row + 1),
+                        timer: Timer::from_seconds(0.3, TimerMode::Once),
+                    });
+            }
+        }
+    }
+}
+
 pub fn handle_change_player(
     mut game_state: ResMut<GameState>,
     mut change_player_events: EventReader<ChangePlayerEvent>,
@@ -310,11 +629,10 @@ pub fn animate_pieces(
 
         if anim.timer.finished() {
             // Convert to static piece - determine player from sprite color
-            let player = if sprite.color == Player::One.color().expect("could not get color") {
-                Player::One
-            } else {
-                Player::Two
-            };
+            let player = Player::SEATS
+                .into_iter()
+                .find(|player| player.color() == Some(sprite.color))
+                .unwrap_or(Player::One);
 
             commands.entity(entity).remove::<AnimatingPiece>();
             commands.entity(entity).insert(GamePiece {
@@ -326,6 +644,23 @@ pub fn animate_pieces(
     }
 }
 
+// Makes the pieces in the winning lines grow and shrink until the next game
+pub fn pulse_winning_pieces(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut pieces: Query<(&GamePiece, &mut Transform)>,
+) {
+    let pulse = 1.0 + 0.15 * (time.elapsed_secs() * 6.0).sin();
+    for (piece, mut transform) in pieces.iter_mut() {
+        let scale = if game_state.winning_cells.contains(&(piece.row, piece.col)) {
+            pulse
+        } else {
+            1.0
+        };
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
 fn ease_out_bounce(t: f32) -> f32 {
     if t < 1.0 / 2.75 {
         7.5625 * t * t
@@ -353,6 +688,7 @@ pub fn cleanup_pieces(
     for _ in reset_events.read() {
         game_state.current_player = Player::One;
         game_state.status = GameStatus::Playing;
+        game_state.clear_board();
         for entity in pieces.iter() {
             commands.entity(entity).despawn();
         }
@@ -391,12 +727,20 @@ pub fn cleanup_pieces(
 pub fn handle_game_over(
     mut commands: Commands,
     mut game_state: ResMut<GameState>,
+    mut score: ResMut<GameScore>,
     mut events: EventReader<GameOverEvent>,
     mut ui_query: Query<Entity, With<RootUINode>>,
     mut surrender_button: Query<Entity, With<SurrenderButton>>,
 ) {
     for event in events.read() {
-        game_state.status = GameStatus::Won(event.winner);
+        game_state.winning_cells = event.lines.concat();
+        match event.winner {
+            Some(winner) => game_state.status = GameStatus::Won(winner),
+            None => {
+                game_state.status = GameStatus::Draw;
+                score.draws += 1;
+            }
+        }
         if let Ok(button) = surrender_button.single_mut() {
             commands.entity(button).despawn();
         }
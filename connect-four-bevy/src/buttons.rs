@@ -1,7 +1,10 @@
 use bevy::prelude::*;
-use connect_four_lib::{player::Player, web_socket::WsMsg};
+use connect_four_lib::{bot::Difficulty, player::Player, web_socket::WsMsg};
 
-use crate::{socket::SendToServerEvent, MyPlayerInfo};
+use crate::{
+    socket::{prompt, SendToServerEvent},
+    MyPlayerInfo,
+};
 
 #[derive(Component)]
 pub struct SurrenderButton;
@@ -9,6 +12,14 @@ pub struct SurrenderButton;
 #[derive(Component)]
 pub struct NewGameButton;
 
+// Asks the server for a computer opponent of the given difficulty
+#[derive(Component)]
+pub struct BotButton(pub Difficulty);
+
+// Opens a private room with a code to share
+#[derive(Component)]
+pub struct PrivateRoomButton;
+
 pub fn surrender_button_action(
     interaction_query: Query<&Interaction, (Changed<Interaction>, With<SurrenderButton>)>,
     my_player: Res<MyPlayerInfo>,
@@ -37,3 +48,29 @@ pub fn new_game_button_action(
         }
     }
 }
+
+pub fn bot_button_action(
+    interaction_query: Query<(&Interaction, &BotButton), Changed<Interaction>>,
+    mut send_to_server_event: EventWriter<SendToServerEvent>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            send_to_server_event.write(SendToServerEvent(WsMsg::ClientRequestBot {
+                difficulty: button.0,
+            }));
+        }
+    }
+}
+
+pub fn private_room_button_action(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PrivateRoomButton>)>,
+    mut send_to_server_event: EventWriter<SendToServerEvent>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            let password = prompt("Password for the new room, leave empty for none")
+                .filter(|password| !password.is_empty());
+            send_to_server_event.write(SendToServerEvent(WsMsg::CreateRoom { password }));
+        }
+    }
+}
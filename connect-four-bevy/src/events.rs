@@ -9,6 +9,16 @@ pub struct PieceDropEvent {
     pub player: Player,
 }
 
+// PopOut: the bottom piece of `column` was removed
+#[derive(Event)]
+pub struct PiecePopEvent {
+    pub column: usize,
+}
+
+// Fog of war: the server sent the board as we can see it, so the drawn pieces need to match
+#[derive(Event)]
+pub struct BoardSyncEvent;
+
 #[derive(Event)]
 pub struct ChangePlayerEvent {
     pub player: Player,
@@ -25,5 +35,14 @@ pub struct PieceAnimationComplete {
 
 #[derive(Event)]
 pub struct GameOverEvent {
-    pub winner: Player,
+    // `None` when the game ended in a draw
+    pub winner: Option<Player>,
+    // (row, col) cells of the lines that ended the game
+    pub lines: Vec<Vec<(usize, usize)>>,
+}
+
+// The server refused something we sent, with a message to show the player
+#[derive(Event)]
+pub struct ServerErrorEvent {
+    pub message: String,
 }
@@ -1,11 +1,13 @@
 use bevy::prelude::*;
-use connect_four_lib::board::BoardArray;
+use connect_four_lib::{board::BoardArray, config::GameConfig, game::Game};
 use std::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Component)]
 pub enum Player {
     One,
     Two,
+    Three,
+    Four,
     Spectator,
 }
 
@@ -14,24 +16,23 @@ impl Display for Player {
         match self {
             Player::One => write!(f, "Red"),
             Player::Two => write!(f, "Yellow"),
+            Player::Three => write!(f, "Green"),
+            Player::Four => write!(f, "Blue"),
             Player::Spectator => write!(f, "Spectator"),
         }
     }
 }
 
 impl Player {
-    pub fn other(self) -> Option<Self> {
-        match self {
-            Player::One => Some(Player::Two),
-            Player::Two => Some(Player::One),
-            Player::Spectator => None,
-        }
-    }
+    // Everyone who can sit at the board, in turn order
+    pub const SEATS: [Player; 4] = [Player::One, Player::Two, Player::Three, Player::Four];
 
     pub fn color(self) -> Option<Color> {
         match self {
             Player::One => Some(Color::srgb(0.8, 0.2, 0.2)),
             Player::Two => Some(Color::srgb(0.9, 0.9, 0.2)),
+            Player::Three => Some(Color::srgb(0.2, 0.7, 0.3)),
+            Player::Four => Some(Color::srgb(0.2, 0.4, 0.9)),
             Player::Spectator => None,
         }
     }
@@ -42,6 +43,8 @@ impl From<&connect_four_lib::player::Player> for Player {
         match value {
             connect_four_lib::player::Player::One => Player::One,
             connect_four_lib::player::Player::Two => Player::Two,
+            connect_four_lib::player::Player::Three => Player::Three,
+            connect_four_lib::player::Player::Four => Player::Four,
             connect_four_lib::player::Player::Spectator => Player::Spectator,
         }
     }
@@ -52,6 +55,8 @@ impl From<Player> for connect_four_lib::player::Player {
         match value {
             Player::One => connect_four_lib::player::Player::One,
             Player::Two => connect_four_lib::player::Player::Two,
+            Player::Three => connect_four_lib::player::Player::Three,
+            Player::Four => connect_four_lib::player::Player::Four,
             Player::Spectator => connect_four_lib::player::Player::Spectator,
         }
     }
@@ -62,6 +67,8 @@ impl From<&Player> for connect_four_lib::player::Player {
         match value {
             Player::One => connect_four_lib::player::Player::One,
             Player::Two => connect_four_lib::player::Player::Two,
+            Player::Three => connect_four_lib::player::Player::Three,
+            Player::Four => connect_four_lib::player::Player::Four,
             Player::Spectator => connect_four_lib::player::Player::Spectator,
         }
     }
@@ -74,23 +81,54 @@ pub enum GameStatus {
     Draw,
 }
 
-type Board = [[Option<Player>; 7]; 6];
+// What a slot on the board holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cell {
+    #[default]
+    Empty,
+    // Neutral cell that pieces land on top of
+    Blocker,
+    Piece(Player),
+}
+
+impl From<&connect_four_lib::board::Cell> for Cell {
+    fn from(value: &connect_four_lib::board::Cell) -> Self {
+        match value {
+            connect_four_lib::board::Cell::Empty => Cell::Empty,
+            connect_four_lib::board::Cell::Blocker => Cell::Blocker,
+            connect_four_lib::board::Cell::Piece(player) => Cell::Piece(player.into()),
+        }
+    }
+}
+
+// Indexed by row then column, row 0 at the top
+type Board = Vec<Vec<Cell>>;
 
 #[derive(Resource, Debug)]
 pub struct GameState {
+    // Board size and line length, sent by the server when joining
+    pub config: GameConfig,
     pub board: Board,
+    // Pieces collected by each seated player in Pop 10, in turn order
+    pub captured: Vec<u32>,
     pub current_player: Player,
     pub status: GameStatus,
     pub move_count: u32,
+    // (row, col) cells of the lines that ended the game, drawn pulsing
+    pub winning_cells: Vec<(usize, usize)>,
 }
 
 impl Default for GameState {
     fn default() -> Self {
+        let config = GameConfig::default();
         Self {
-            board: [[None; 7]; 6],
+            config,
+            board: vec![vec![Cell::Empty; config.width]; config.height],
+            captured: vec![0; config.players],
             current_player: Player::One,
             status: GameStatus::Playing,
             move_count: 0,
+            winning_cells: Vec::new(),
         }
     }
 }
@@ -105,34 +143,62 @@ impl GameState {
     }
 
     pub fn is_column_full(&self, col: usize) -> bool {
-        if col >= 7 {
-            return true;
+        match self.board.first().and_then(|top| top.get(col)) {
+            Some(cell) => *cell != Cell::Empty,
+            None => true,
         }
-        self.board[0][col].is_some()
+    }
+
+    // Cells outside the board count as empty
+    pub fn get_cell(&self, row: usize, col: usize) -> Cell {
+        self.board
+            .get(row)
+            .and_then(|cells| cells.get(col))
+            .copied()
+            .unwrap_or_default()
     }
 
     pub fn get_piece(&self, row: usize, col: usize) -> Option<Player> {
-        if row < 6 && col < 7 {
-            self.board[row][col]
-        } else {
-            None
+        match self.get_cell(row, col) {
+            Cell::Piece(player) => Some(player),
+            _ => None,
+        }
+    }
+
+    // Empties the board apart from the blockers every game starts with, which the client
+    // works out from the config the same way the server does
+    pub fn clear_board(&mut self) {
+        let config = self.config;
+        self.get_state_from_lib(&Game::new(config).get_board().get_board_array(), config);
+        self.captured = vec![0; self.config.players];
+        self.winning_cells.clear();
+    }
+
+    pub fn place_piece(&mut self, row: usize, col: usize, player: Player) {
+        if let Some(cell) = self.board.get_mut(row).and_then(|cells| cells.get_mut(col)) {
+            *cell = Cell::Piece(player);
         }
     }
 
-    pub fn get_state_from_lib(
-        &mut self,
-        board: &[[std::option::Option<connect_four_lib::player::Player>; 7]; 6],
-    ) {
-        let mut new_board: [[Option<Player>; 7]; 6] = [[None; 7]; 6];
-        for (i, row) in board.iter().enumerate() {
-            for (j, col) in row.iter().enumerate() {
-                if let Some(player) = col {
-                    new_board[i][j] = Some(player.into());
-                }
-            }
+    // PopOut: removes the bottom piece of `col` and moves everything above it down a row
+    pub fn pop_piece(&mut self, col: usize) {
+        if col >= self.config.width {
+            return;
+        }
+        for row in (1..self.board.len()).rev() {
+            self.board[row][col] = self.board[row - 1][col];
         }
+        if let Some(top) = self.board.first_mut() {
+            top[col] = Cell::Empty;
+        }
+    }
 
-        self.board = new_board;
+    pub fn get_state_from_lib(&mut self, board: &BoardArray, config: GameConfig) {
+        self.config = config;
+        self.board = board
+            .iter()
+            .map(|row| row.iter().map(Cell::from).collect())
+            .collect();
     }
 }
 
@@ -147,4 +213,45 @@ mod tests {
         assert_eq!(game.status, GameStatus::Playing);
         assert_eq!(game.move_count, 0);
     }
+
+    #[test]
+    fn test_state_from_lib_board() {
+        let config = GameConfig::new(9, 7, 4).unwrap();
+        let mut board = connect_four_lib::board::Board::new(config.width, config.height);
+        board.insert_piece(
+            connect_four_lib::board::Row::new(6),
+            connect_four_lib::board::Column::new(8),
+            connect_four_lib::player::Player::Two,
+        );
+        let mut game = GameState::new();
+        game.get_state_from_lib(&board.get_board_array(), config);
+        assert_eq!(game.config, config);
+        assert_eq!(game.get_piece(6, 8), Some(Player::Two));
+        assert_eq!(game.get_piece(7, 8), None);
+        assert!(!game.is_column_full(8));
+        assert!(game.is_column_full(9));
+    }
+
+    #[test]
+    fn test_blockers_survive_a_new_game() {
+        let config = GameConfig::default()
+            .with_blockers(connect_four_lib::config::Blockers::fixed(7, [(5, 3)]).unwrap());
+        let mut game = GameState::new();
+        game.config = config;
+        game.place_piece(5, 0, Player::One);
+        game.clear_board();
+        assert_eq!(game.get_cell(5, 3), Cell::Blocker);
+        assert_eq!(game.get_piece(5, 3), None);
+        assert_eq!(game.get_cell(5, 0), Cell::Empty);
+    }
+
+    #[test]
+    fn test_pop_piece() {
+        let mut game = GameState::new();
+        game.place_piece(5, 2, Player::One);
+        game.place_piece(4, 2, Player::Two);
+        game.pop_piece(2);
+        assert_eq!(game.get_piece(5, 2), Some(Player::Two));
+        assert_eq!(game.get_piece(4, 2), None);
+    }
 }
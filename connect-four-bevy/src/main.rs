@@ -6,16 +6,21 @@ mod board;
 mod buttons;
 mod events;
 mod game_logic;
+mod score_four;
 mod socket;
 mod ui;
 
 use board::*;
 use events::*;
 use game_logic::*;
+use score_four::{ScoreFourPlugin, ViewMode};
 use ui::*;
 use uuid::Uuid;
 
-use crate::buttons::{new_game_button_action, surrender_button_action};
+use crate::buttons::{
+    bot_button_action, new_game_button_action, private_room_button_action,
+    surrender_button_action,
+};
 
 fn main() {
     // let args = Args::parse();
@@ -23,6 +28,7 @@ fn main() {
     let my_player = MyPlayerInfo {
         id: None,
         color: None,
+        room: None,
     };
     App::new()
         .insert_resource(MyPlayerInfo { ..my_player })
@@ -36,26 +42,41 @@ fn main() {
             ..default()
         }))
         .add_plugins(SocketIOPlugin)
+        .add_plugins(ScoreFourPlugin)
         .init_resource::<GameState>()
         .init_resource::<GameScore>()
         .add_event::<PieceDropEvent>()
+        .add_event::<PiecePopEvent>()
         .add_event::<ChangePlayerEvent>()
+        .add_event::<BoardSyncEvent>()
         .add_event::<GameResetEvent>()
         .add_event::<PieceAnimationComplete>()
         .add_event::<GameOverEvent>()
+        .add_event::<ServerErrorEvent>()
         .add_systems(Startup, (setup_camera, setup_board, setup_ui))
         .add_systems(
             Update,
             (
-                handle_input,
+                handle_input.run_if(in_state(ViewMode::Board2d)),
+                resize_board.before(handle_piece_drop),
                 handle_piece_drop,
+                handle_piece_pop,
+                sync_pieces.after(handle_piece_drop),
+                update_ghost_pieces.after(resize_board),
+                update_blockers.after(resize_board),
                 handle_change_player.after(handle_piece_drop),
                 handle_game_over,
                 ui::update_my_turn_indicator,
                 animate_pieces,
+                pulse_winning_pieces,
                 cleanup_pieces,
                 surrender_button_action,
                 new_game_button_action,
+                bot_button_action,
+                private_room_button_action,
+                ui::update_room_text,
+                ui::show_error_toasts,
+                ui::expire_toasts,
             ),
         )
         .run();
@@ -71,6 +92,8 @@ pub enum GameSide {
 pub struct MyPlayerInfo {
     pub id: Option<String>,
     pub color: Option<Player>,
+    // Room on the server whose game is on screen
+    pub room: Option<String>,
 }
 
 fn setup_camera(mut commands: Commands) {
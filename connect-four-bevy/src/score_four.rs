@@ -0,0 +1,329 @@
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll};
+use bevy::prelude::*;
+use connect_four_lib::game::GameStatus;
+use connect_four_lib::score_four::{Pole, ScoreFourGame, SIZE};
+
+use crate::game_logic::Player;
+use crate::ui::RootUINode;
+
+// Distance between neighbouring poles, and how much of it a piece takes up
+const POLE_SPACING: f32 = 1.0;
+const PIECE_RADIUS: f32 = 0.35;
+const POLE_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
+const BASE_COLOR: Color = Color::srgb(0.2, 0.4, 0.8);
+// How close to a pole a click has to be to drop a piece on it
+const PICK_DISTANCE: f32 = 0.45;
+
+// Which game is on screen: the server's game on the 2D board, or a game of Score Four
+// between two players sharing this client
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ViewMode {
+    #[default]
+    Board2d,
+    ScoreFour3d,
+}
+
+#[derive(Resource, Default)]
+pub struct ScoreFourState(pub ScoreFourGame);
+
+// Everything spawned for the 3D view, despawned when going back to the 2D board
+#[derive(Component)]
+struct ScoreFourEntity;
+
+#[derive(Component)]
+struct ScoreFourPiece;
+
+#[derive(Component)]
+struct ScoreFourText;
+
+// Camera circling the middle of the poles
+#[derive(Component)]
+struct OrbitCamera {
+    yaw: f32,
+    pitch: f32,
+    radius: f32,
+}
+
+impl OrbitCamera {
+    fn transform(&self) -> Transform {
+        let rotation = Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0);
+        Transform::from_translation(center() + rotation * Vec3::new(0.0, 0.0, self.radius))
+            .looking_at(center(), Vec3::Y)
+    }
+}
+
+pub struct ScoreFourPlugin;
+
+impl Plugin for ScoreFourPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<ViewMode>()
+            .init_resource::<ScoreFourState>()
+            .add_systems(Update, switch_view_mode)
+            .add_systems(OnEnter(ViewMode::ScoreFour3d), enter_score_four)
+            .add_systems(OnExit(ViewMode::ScoreFour3d), exit_score_four)
+            .add_systems(
+                Update,
+                (orbit_camera, drop_on_pole, update_score_four_text)
+                    .run_if(in_state(ViewMode::ScoreFour3d)),
+            );
+    }
+}
+
+// Point the camera orbits around, halfway up the poles
+fn center() -> Vec3 {
+    Vec3::new(0.0, PIECE_RADIUS * SIZE as f32, 0.0)
+}
+
+fn pole_position(pole: Pole) -> Vec3 {
+    let offset = (SIZE as f32 - 1.0) / 2.0;
+    Vec3::new(
+        (pole.x as f32 - offset) * POLE_SPACING,
+        0.0,
+        (pole.y as f32 - offset) * POLE_SPACING,
+    )
+}
+
+fn piece_position(pole: Pole, level: usize) -> Vec3 {
+    pole_position(pole) + Vec3::Y * PIECE_RADIUS * (2.0 * level as f32 + 1.0)
+}
+
+// 3 switches to Score Four and 2 back to the 2D board
+fn switch_view_mode(keys: Res<ButtonInput<KeyCode>>, mut next: ResMut<NextState<ViewMode>>) {
+    if keys.just_pressed(KeyCode::Digit3) {
+        next.set(ViewMode::ScoreFour3d);
+    } else if keys.just_pressed(KeyCode::Digit2) {
+        next.set(ViewMode::Board2d);
+    }
+}
+
+fn enter_score_four(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    state: Res<ScoreFourState>,
+    mut cameras_2d: Query<&mut Camera, With<Camera2d>>,
+    mut ui: Query<&mut Visibility, With<RootUINode>>,
+) {
+    for mut camera in cameras_2d.iter_mut() {
+        camera.is_active = false;
+    }
+    for mut visibility in ui.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+
+    let orbit = OrbitCamera {
+        yaw: 0.6,
+        pitch: 0.5,
+        radius: 8.0,
+    };
+    commands.spawn((
+        Camera3d::default(),
+        orbit.transform(),
+        orbit,
+        IsDefaultUiCamera,
+        ScoreFourEntity,
+    ));
+    commands.spawn((
+        DirectionalLight {
+            illuminance: 8000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ScoreFourEntity,
+    ));
+
+    let side = SIZE as f32 * POLE_SPACING;
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(side, 0.2, side))),
+        MeshMaterial3d(materials.add(BASE_COLOR)),
+        Transform::from_xyz(0.0, -0.1, 0.0),
+        ScoreFourEntity,
+    ));
+    let pole_height = PIECE_RADIUS * 2.0 * SIZE as f32 + 0.3;
+    let pole_mesh = meshes.add(Cylinder::new(0.06, pole_height));
+    let pole_material = materials.add(POLE_COLOR);
+    for pole in Pole::all() {
+        commands.spawn((
+            Mesh3d(pole_mesh.clone()),
+            MeshMaterial3d(pole_material.clone()),
+            Transform::from_translation(pole_position(pole) + Vec3::Y * pole_height / 2.0),
+            ScoreFourEntity,
+        ));
+    }
+
+    // Pieces from a game left earlier
+    let board = state.0.get_board();
+    for pole in Pole::all() {
+        for level in 0..board.pole_height(pole) {
+            if let Some(player) = board.get(pole, level) {
+                spawn_piece(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    pole,
+                    level,
+                    player,
+                );
+            }
+        }
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        ScoreFourText,
+        ScoreFourEntity,
+    ));
+}
+
+fn exit_score_four(
+    mut commands: Commands,
+    entities: Query<Entity, With<ScoreFourEntity>>,
+    mut cameras_2d: Query<&mut Camera, With<Camera2d>>,
+    mut ui: Query<&mut Visibility, With<RootUINode>>,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn();
+    }
+    for mut camera in cameras_2d.iter_mut() {
+        camera.is_active = true;
+    }
+    for mut visibility in ui.iter_mut() {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn spawn_piece(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    pole: Pole,
+    level: usize,
+    player: connect_four_lib::player::Player,
+) {
+    let Some(color) = Player::from(&player).color() else {
+        return;
+    };
+    commands.spawn((
+        Mesh3d(meshes.add(Sphere::new(PIECE_RADIUS))),
+        MeshMaterial3d(materials.add(color)),
+        Transform::from_translation(piece_position(pole, level)),
+        ScoreFourPiece,
+        ScoreFourEntity,
+    ));
+}
+
+// Dragging with the right mouse button circles the board, scrolling zooms
+fn orbit_camera(
+    buttons: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    scroll: Res<AccumulatedMouseScroll>,
+    mut cameras: Query<(&mut OrbitCamera, &mut Transform)>,
+) {
+    for (mut orbit, mut transform) in cameras.iter_mut() {
+        if buttons.pressed(MouseButton::Right) {
+            orbit.yaw -= motion.delta.x * 0.005;
+            orbit.pitch = (orbit.pitch + motion.delta.y * 0.005).clamp(0.05, 1.5);
+        }
+        orbit.radius = (orbit.radius - scroll.delta.y * 0.5).clamp(4.0, 16.0);
+        *transform = orbit.transform();
+    }
+}
+
+// Left click drops a piece down the pole under the cursor, R starts a new game
+#[allow(clippy::too_many_arguments)]
+fn drop_on_pole(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut state: ResMut<ScoreFourState>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCamera>>,
+    pieces: Query<Entity, With<ScoreFourPiece>>,
+) {
+    if keys.just_pressed(KeyCode::KeyR) {
+        state.0 = ScoreFourGame::new();
+        for entity in pieces.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.single(), cameras.single()) else {
+        return;
+    };
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    let Some(pole) = pick_pole(ray) else {
+        return;
+    };
+    let player = state.0.current_player();
+    match state.0.make_move(pole) {
+        Ok(level) => spawn_piece(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            pole,
+            level,
+            player,
+        ),
+        Err(e) => info!("can't drop on pole {:?}: {}", pole, e),
+    }
+}
+
+// The pole passing closest to the ray, seen from above
+fn pick_pole(ray: Ray3d) -> Option<Pole> {
+    let origin = ray.origin.xz();
+    let direction = ray.direction.xz().normalize_or_zero();
+    Pole::all()
+        .map(|pole| {
+            let to_pole = pole_position(pole).xz() - origin;
+            let distance = if direction == Vec2::ZERO {
+                to_pole.length()
+            } else {
+                direction.perp_dot(to_pole).abs()
+            };
+            (pole, distance, to_pole.dot(direction))
+        })
+        .filter(|(_, distance, along)| *distance < PICK_DISTANCE && *along >= 0.0)
+        // The nearest pole wins when several line up
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(pole, _, _)| pole)
+}
+
+fn update_score_four_text(
+    state: Res<ScoreFourState>,
+    mut text: Query<&mut Text, With<ScoreFourText>>,
+) {
+    let Ok(mut text) = text.single_mut() else {
+        return;
+    };
+    let status = match state.0.status() {
+        GameStatus::Won(winner) => format!("{} wins!", Player::from(&winner)),
+        GameStatus::Draw => "It's a draw!".to_owned(),
+        GameStatus::Playing => format!("{}'s turn", Player::from(&state.0.current_player())),
+    };
+    **text = format!(
+        "Score Four: {status}\nLeft click a pole to drop, drag with the right button to look \
+         around\nR starts again, 2 goes back to the board"
+    );
+}
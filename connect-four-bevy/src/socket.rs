@@ -2,7 +2,7 @@
 
 use async_channel::{Receiver, Sender};
 use bevy::prelude::*;
-use connect_four_lib::web_socket::WsMsg;
+use connect_four_lib::web_socket::{ErrorCode, WsMsg};
 use futures::{SinkExt, StreamExt};
 
 #[cfg(target_arch = "wasm32")]
@@ -16,7 +16,10 @@ use bevy_tokio_tasks::TokioTasksRuntime;
 pub use tokio_tungstenite::connect_async;
 
 use crate::{
-    events::{ChangePlayerEvent, GameOverEvent, GameResetEvent, PieceDropEvent},
+    events::{
+        BoardSyncEvent, ChangePlayerEvent, GameOverEvent, GameResetEvent, PieceDropEvent,
+        PiecePopEvent, ServerErrorEvent,
+    },
     game_logic::{GameState, GameStatus, Player},
     ui::setup_ui,
     MyPlayerInfo,
@@ -97,6 +100,11 @@ fn setup_socketio_client(
     commands.insert_resource(SocketMessageSender(outbound_sender));
     commands.insert_resource(SocketMessageReceiver(inbound_receiver));
 
+    // Sent once the connection is up, after the server has put us in the lobby
+    if let Some(join_room) = room_to_join() {
+        sender.write(SendToServerEvent(join_room));
+    }
+
     #[cfg(target_arch = "wasm32")]
     spawn_local(async move {
         info!("starting websocket connection");
@@ -168,6 +176,42 @@ fn setup_socketio_client(
     }
 }
 
+// Private room to go straight into, from a `/r/KQ7-PLM` link on the web or the `ROOM` and
+// `ROOM_PASSWORD` environment variables when running natively
+fn room_to_join() -> Option<WsMsg> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let path = web_sys::window()?.location().pathname().ok()?;
+        let room = path.strip_prefix("/r/")?.trim_end_matches('/');
+        // A password is asked for if the room turns out to need one
+        (!room.is_empty()).then(|| WsMsg::JoinRoom {
+            room: room.to_owned(),
+            password: None,
+        })
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Some(WsMsg::JoinRoom {
+            room: std::env::var("ROOM").ok()?,
+            password: std::env::var("ROOM_PASSWORD").ok(),
+        })
+    }
+}
+
+// Asks the player to type something in. There's no text input outside the browser, so
+// natively this is always `None`.
+pub fn prompt(message: &str) -> Option<String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()?.prompt_with_message(message).ok()?
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        info!("can't ask for input here: {}", message);
+        None
+    }
+}
+
 // System to handle outbound messages (Bevy -> Server)
 fn send_messages_to_server(
     mut events: EventReader<SendToServerEvent>,
@@ -191,14 +235,19 @@ fn receive_messages_from_server(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_server_messages(
     mut socket_events: EventReader<SocketMessageEvent>,
     mut game_state: ResMut<GameState>,
     mut my_player: ResMut<MyPlayerInfo>,
     mut piece_event_writer: EventWriter<PieceDropEvent>,
+    mut pop_event_writer: EventWriter<PiecePopEvent>,
     mut change_player_event_writer: EventWriter<ChangePlayerEvent>,
+    mut board_sync_event_writer: EventWriter<BoardSyncEvent>,
     mut game_over_event_writer: EventWriter<GameOverEvent>,
     mut reset_event_writer: EventWriter<GameResetEvent>,
+    mut error_event_writer: EventWriter<ServerErrorEvent>,
+    mut server_event_writer: EventWriter<SendToServerEvent>,
 ) {
     for event in socket_events.read() {
         match &event.0 {
@@ -207,27 +256,29 @@ fn handle_server_messages(
                 client_player,
                 active_player,
                 game_board,
+                config,
+                captured,
             } => {
                 info!("Player {} has joined as color {:?}", id, client_player);
-                game_state.get_state_from_lib(game_board);
+                game_state.get_state_from_lib(game_board, *config);
+                game_state.captured = captured.clone();
                 if my_player.id.is_none() {
                     my_player.id = Some(id.clone());
                     my_player.color = Some(client_player.into());
-                    for (i, row) in game_state.board.iter().enumerate() {
-                        for (j, col) in row.iter().enumerate() {
-                            if let Some(piece) = col {
-                                piece_event_writer.write(PieceDropEvent {
-                                    column: j,
-                                    row: i,
-                                    player: *piece,
-                                });
-                            }
-                        }
-                    }
+                    // Draws the pieces already played, and clears any left from another room
+                    board_sync_event_writer.write(BoardSyncEvent);
                     game_state.current_player = active_player.into();
                     game_state.status = GameStatus::Playing;
                 }
             }
+            WsMsg::RoomJoined { room } => {
+                info!("joined room {}", room);
+                // The join message that follows seats us again and brings the room's board
+                my_player.id = None;
+                my_player.color = None;
+                my_player.room = Some(room.clone());
+                game_state.winning_cells.clear();
+            }
             WsMsg::PlayerLeave { id } => {
                 info!("Player {} has left", id);
             }
@@ -236,25 +287,98 @@ fn handle_server_messages(
                 col,
                 row,
                 active_player,
+                next_player,
             } => {
                 info!(
                     "Player {} has made a move on column {:?} and row {:?}",
                     id, col, row
                 );
                 let player: Player = Player::from(active_player);
+                game_state.place_piece(*row, *col, player);
                 piece_event_writer.write(PieceDropEvent {
                     column: col.to_owned(),
                     row: row.to_owned(),
                     player,
                 });
                 change_player_event_writer.write(ChangePlayerEvent {
-                    player: player.other().expect("unable to get other player"),
+                    player: next_player.into(),
                 });
             }
-            WsMsg::GameOver { winner } => {
+            WsMsg::ServerPop {
+                id,
+                col,
+                active_player,
+                next_player,
+                captured,
+            } => {
+                info!(
+                    "Player {} ({}) has popped a piece out of column {:?}",
+                    id,
+                    Player::from(active_player),
+                    col
+                );
+                game_state.pop_piece(*col);
+                game_state.captured = captured.clone();
+                pop_event_writer.write(PiecePopEvent { column: *col });
+                change_player_event_writer.write(ChangePlayerEvent {
+                    player: next_player.into(),
+                });
+            }
+            WsMsg::ServerView {
+                game_board,
+                next_player,
+            } => {
+                let config = game_state.config;
+                game_state.get_state_from_lib(game_board, config);
+                board_sync_event_writer.write(BoardSyncEvent);
+                change_player_event_writer.write(ChangePlayerEvent {
+                    player: next_player.into(),
+                });
+            }
+            WsMsg::GameOver { winner, lines } => {
                 let player = Player::from(winner);
                 info!("Player {} wins the game!", player);
-                game_over_event_writer.write(GameOverEvent { winner: player });
+                game_over_event_writer.write(GameOverEvent {
+                    winner: Some(player),
+                    lines: lines.clone(),
+                });
+            }
+            WsMsg::PlayerEliminated {
+                player,
+                next_player,
+            } => {
+                info!("Player {} has surrendered", Player::from(player));
+                change_player_event_writer.write(ChangePlayerEvent {
+                    player: next_player.into(),
+                });
+            }
+            WsMsg::GameDraw => {
+                info!("The game is a draw!");
+                game_over_event_writer.write(GameOverEvent {
+                    winner: None,
+                    lines: Vec::new(),
+                });
+            }
+            WsMsg::Error {
+                code,
+                message,
+                in_reply_to,
+            } => {
+                warn!("the server sent back an error {:?}: {}", code, message);
+                error_event_writer.write(ServerErrorEvent {
+                    message: message.clone(),
+                });
+                // Try the private room again with the password, unless the player gives up
+                if let (ErrorCode::WrongPassword, Some(WsMsg::JoinRoom { room, .. })) =
+                    (code, in_reply_to.as_deref())
+                {
+                    if let Some(password) = prompt(&format!("Password for room {room}")) {
+                        server_event_writer.write(SendToServerEvent(WsMsg::JoinRoom {
+                            room: room.clone(),
+                            password: Some(password),
+                        }));
+                    }
+                }
             }
             WsMsg::NewGame => {
                 info!("restarting the game");
@@ -1,5 +1,11 @@
-use crate::{buttons::SurrenderButton, game_logic::*};
+use crate::{
+    buttons::{BotButton, PrivateRoomButton, SurrenderButton},
+    events::ServerErrorEvent,
+    game_logic::*,
+};
 use bevy::prelude::*;
+use connect_four_lib::{bot::Difficulty, config::Rules, web_socket::LOBBY};
+use strum::IntoEnumIterator;
 
 #[derive(Component)]
 pub struct MyTurnIndicator;
@@ -19,6 +25,16 @@ pub struct GameStatusText;
 pub struct CurrentPlayerText;
 #[derive(Component)]
 pub struct ScoreText;
+// Code of the private room we're in, for sharing
+#[derive(Component)]
+pub struct RoomText;
+
+// How long an error from the server stays on screen
+const TOAST_SECONDS: f32 = 3.0;
+
+// Message from the server shown at the bottom of the window until its timer runs out
+#[derive(Component)]
+pub struct Toast(Timer);
 
 pub fn setup_ui(mut commands: Commands) {
     // Root UI node for layout
@@ -50,6 +66,77 @@ pub fn setup_ui(mut commands: Commands) {
                 ))
                 .insert(MyTurnIndicator);
 
+            // Computer opponent buttons
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        Text::new("Play a bot:"),
+                        TextFont {
+                            font_size: 16.0,
+                            ..Default::default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                    for difficulty in Difficulty::iter() {
+                        row.spawn((
+                            Button,
+                            Node {
+                                margin: UiRect::all(Val::Px(5.0)),
+                                padding: UiRect::all(Val::Px(5.0)),
+                                ..Default::default()
+                            },
+                            BackgroundColor(Color::BLACK),
+                            BotButton(difficulty),
+                        ))
+                        .with_children(|button| {
+                            button.spawn((
+                                Text::new(difficulty.to_string()),
+                                TextFont {
+                                    font_size: 16.0,
+                                    ..Default::default()
+                                },
+                                TextColor(Color::WHITE),
+                            ));
+                        });
+                    }
+
+                    // Private room to play with whoever the code is shared with
+                    row.spawn((
+                        Button,
+                        Node {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            padding: UiRect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::BLACK),
+                        PrivateRoomButton,
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new("Private Room"),
+                            TextFont {
+                                font_size: 16.0,
+                                ..Default::default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+                    row.spawn((
+                        Text::new(""),
+                        TextFont {
+                            font_size: 16.0,
+                            ..Default::default()
+                        },
+                        TextColor(Color::WHITE),
+                        RoomText,
+                    ));
+                });
+
             // Spacer
             parent.spawn((
                 Node {
@@ -90,20 +177,98 @@ pub fn update_my_turn_indicator(
     my_player: Res<crate::MyPlayerInfo>,
     mut q: Query<&mut Text, With<MyTurnIndicator>>,
 ) {
-    let is_my_turn = match my_player.color {
-        Some(Player::One) => game_state.current_player == Player::One,
-        Some(Player::Two) => game_state.current_player == Player::Two,
-        _ => false,
-    };
+    let is_my_turn = my_player
+        .color
+        .is_some_and(|color| color != Player::Spectator && color == game_state.current_player);
     if let Ok(mut text) = q.single_mut() {
+        let misere = game_state.config.misere;
         if let GameStatus::Won(winner) = game_state.status {
-            **text = format!("{} wins!", winner);
+            if misere {
+                **text = format!("{} wins, their opponent connected four!", winner);
+            } else {
+                **text = format!("{} wins!", winner);
+            }
+        } else if game_state.status == GameStatus::Draw {
+            **text = "It's a draw!".to_owned();
         } else if my_player.color == Some(Player::Spectator) {
             **text = "Spectating...".to_owned();
+        } else if is_my_turn && misere {
+            **text = "Your turn! Don't connect four".to_owned();
         } else if is_my_turn {
             **text = "Your turn!".to_owned();
         } else {
             **text = "Waiting...".to_owned();
         }
+        if misere && game_state.status == GameStatus::Playing && !is_my_turn {
+            text.push_str(" (Misère)");
+        }
+        if game_state.config.rules == Rules::Pop10 {
+            let scores: Vec<String> = Player::SEATS
+                .iter()
+                .zip(&game_state.captured)
+                .map(|(player, captured)| format!("{player} {captured}"))
+                .collect();
+            text.push_str(&format!(" ({})", scores.join(" - ")));
+        }
+    }
+}
+
+pub fn update_room_text(
+    my_player: Res<crate::MyPlayerInfo>,
+    mut q: Query<&mut Text, With<RoomText>>,
+) {
+    if !my_player.is_changed() {
+        return;
+    }
+    if let Ok(mut text) = q.single_mut() {
+        **text = match my_player.room.as_deref() {
+            Some(room) if room != LOBBY => {
+                format!("Room {room}, invite with /r/{room}")
+            }
+            _ => String::new(),
+        };
+    }
+}
+
+// Shows each error the server sends back in place of the last one
+pub fn show_error_toasts(
+    mut commands: Commands,
+    mut errors: EventReader<ServerErrorEvent>,
+    toasts: Query<Entity, With<Toast>>,
+) {
+    let Some(error) = errors.read().last() else {
+        return;
+    };
+    for entity in toasts.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.spawn((
+        Text::new(error.message.clone()),
+        TextFont {
+            font_size: 20.0,
+            ..Default::default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.0),
+            left: Val::Px(20.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.6, 0.1, 0.1, 0.9)),
+        Toast(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once)),
+    ));
+}
+
+pub fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
     }
 }
@@ -0,0 +1,97 @@
+use connect_four_lib::{
+    board::{Board, Column, Row},
+    game::Game,
+    player::Player,
+};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+// The cell-by-cell scan `Game::check_for_winner` used before the board became a bitboard,
+// kept here so the two can be compared
+fn scan_for_winner(board: &Board) -> Option<Player> {
+    let row_idx = |row: Row| usize::from(row);
+    let col_idx = |col: Column| usize::from(col);
+
+    for row in board.rows() {
+        for col in board.columns() {
+            if let Some(player) = board.get(row, col) {
+                // Horizontal
+                if col_idx(col) <= 3
+                    && (0..4).all(|i| {
+                        board.get(row, board.columns().nth(col_idx(col) + i).unwrap())
+                            == Some(player)
+                    })
+                {
+                    return Some(player);
+                }
+                // Vertical
+                if row_idx(row) <= 2
+                    && (0..4).all(|i| {
+                        board.get(board.rows().nth(row_idx(row) + i).unwrap(), col) == Some(player)
+                    })
+                {
+                    return Some(player);
+                }
+                // Diagonal down-right
+                if col_idx(col) <= 3
+                    && row_idx(row) <= 2
+                    && (0..4).all(|i| {
+                        board.get(
+                            board.rows().nth(row_idx(row) + i).unwrap(),
+                            board.columns().nth(col_idx(col) + i).unwrap(),
+                        ) == Some(player)
+                    })
+                {
+                    return Some(player);
+                }
+                // Diagonal down-left
+                if col_idx(col) >= 3
+                    && row_idx(row) <= 2
+                    && (0..4).all(|i| {
+                        board.get(
+                            board.rows().nth(row_idx(row) + i).unwrap(),
+                            board.columns().nth(col_idx(col) - i).unwrap(),
+                        ) == Some(player)
+                    })
+                {
+                    return Some(player);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn game_from_columns(columns: &[usize]) -> Game {
+    let mut game = Game::default();
+    for col in columns {
+        game.make_move(game.current_player(), &Column::new(*col))
+            .unwrap();
+    }
+    game
+}
+
+fn bench_win_check(c: &mut Criterion) {
+    // A busy board with no four in a row, which is the worst case for the scan
+    let no_winner = game_from_columns(&[
+        1, 4, 6, 6, 6, 0, 2, 0, 3, 6, 3, 3, 5, 3, 6, 1, 0, 3, 0, 6, 3, 4, 5, 0,
+    ]);
+    // Player one wins with the last piece, in the bottom right corner
+    let late_winner = game_from_columns(&[3, 0, 4, 0, 5, 1, 6]);
+
+    let mut group = c.benchmark_group("check_for_winner");
+    for (name, game) in [("no_winner", &no_winner), ("late_winner", &late_winner)] {
+        assert_eq!(game.check_for_winner(), scan_for_winner(&game.get_board()));
+        group.bench_function(format!("bitboard/{name}"), |b| {
+            b.iter(|| black_box(game).check_for_winner())
+        });
+        group.bench_function(format!("scan/{name}"), |b| {
+            let board = game.get_board();
+            b.iter(|| scan_for_winner(black_box(&board)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_win_check);
+criterion_main!(benches);
@@ -0,0 +1,301 @@
+use crate::{
+    board::{Cell, Column, Row},
+    config::{GameConfig, Rules},
+    errors::GameError,
+    game::Game,
+    player::Player,
+};
+
+// Columns closer to the middle take part in more lines, so they are searched first to get
+// alpha-beta cutoffs early. Alternates outwards from the center, left side first.
+pub(crate) fn column_order(width: usize) -> impl Iterator<Item = Column> {
+    let center = width / 2;
+    (0..width).map(move |i| {
+        let offset = i.div_ceil(2);
+        if i % 2 == 1 {
+            Column::new(center - offset)
+        } else {
+            Column::new(center + offset)
+        }
+    })
+}
+
+// Anything that can pick a move for the player whose turn it is, so the server and client
+// can host any of the engines the same way
+pub trait Agent {
+    // Returns `None` if the game is over
+    fn choose_move(&mut self, game: &Game) -> Option<Column>;
+}
+
+// The engines only drop pieces, play against a single opponent and look at the whole board.
+// Games they can't play that way are turned down before they get stuck or cheat.
+pub fn check_supported(config: &GameConfig) -> Result<(), GameError> {
+    if matches!(config.rules, Rules::PopOut | Rules::Pop10) {
+        return Err(GameError::UnsupportedByBot(format!(
+            "{} rules",
+            config.rules
+        )));
+    }
+    if config.players > 2 {
+        return Err(GameError::UnsupportedByBot(format!(
+            "games with {} players",
+            config.players
+        )));
+    }
+    if config.fog_of_war {
+        return Err(GameError::UnsupportedByBot("fog of war".to_owned()));
+    }
+    Ok(())
+}
+
+// Score for winning on the next move. Wins further down the tree score one less per ply
+// so the engine prefers quick wins and slow losses.
+pub const WIN_SCORE: i32 = 100_000;
+
+// Negamax search with alpha-beta pruning that looks `depth` moves ahead and falls back to
+// a heuristic evaluation of the board when it runs out of depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Negamax {
+    pub depth: u32,
+}
+
+impl Default for Negamax {
+    fn default() -> Self {
+        Self::new(6)
+    }
+}
+
+impl Negamax {
+    pub fn new(depth: u32) -> Self {
+        Negamax {
+            depth: depth.max(1),
+        }
+    }
+
+    // Returns the best column for the player whose turn it is, along with its score from
+    // that player's point of view, or `None` if the game is over
+    pub fn best_move(&self, game: &Game) -> Option<(Column, i32)> {
+        if game.is_over() {
+            return None;
+        }
+        let mut game = game.clone();
+        let mut best: Option<(Column, i32)> = None;
+        let mut alpha = -WIN_SCORE - 1;
+        let beta = WIN_SCORE + 1;
+
+        for col in column_order(game.config().width) {
+            let Some(score) = self.score_move(&mut game, col, self.depth, alpha, beta, 0) else {
+                continue;
+            };
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((col, score));
+            }
+            alpha = alpha.max(score);
+        }
+        best
+    }
+
+    fn negamax(&self, game: &mut Game, depth: u32, mut alpha: i32, beta: i32, ply: u32) -> i32 {
+        let mut best = -WIN_SCORE - 1;
+        for col in column_order(game.config().width) {
+            let Some(score) = self.score_move(game, col, depth, alpha, beta, ply) else {
+                continue;
+            };
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // Plays `col`, scores the result for the player that moved and takes the move back.
+    // Returns `None` if the column is full.
+    fn score_move(
+        &self,
+        game: &mut Game,
+        col: Column,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+        ply: u32,
+    ) -> Option<i32> {
+        let player = game.current_player();
+        game.make_move(game.current_player(), &col).ok()?;
+        // In misère the move that makes a line loses
+        let score = if let Some(winner) = game.get_winner() {
+            let score = WIN_SCORE - ply as i32;
+            if winner == player { score } else { -score }
+        } else if game.is_over() {
+            0
+        } else if depth <= 1 {
+            evaluate(game, player)
+        } else {
+            -self.negamax(game, depth - 1, -beta, -alpha, ply + 1)
+        };
+        game.undo();
+        Some(score)
+    }
+}
+
+impl Agent for Negamax {
+    fn choose_move(&mut self, game: &Game) -> Option<Column> {
+        self.best_move(game).map(|(col, _)| col)
+    }
+}
+
+// Heuristic score of the board for `player`: every window of `connect` cells that only one
+// player has pieces in counts towards that player, weighted by how full it is. Windows carry
+// on across the edge of a cylinder board, and windows with a blocker in them never count.
+// In misère a nearly full window is a liability, so the score is flipped.
+pub fn evaluate(game: &Game, player: Player) -> i32 {
+    let board = game.get_board();
+    let connect = game.config().connect as isize;
+    let (height, width) = (board.height() as isize, board.width() as isize);
+    let wrap = board.wraps_around();
+    let mut score = 0;
+
+    // Pieces in the center column are worth a little on their own
+    let center = Column::new(board.width() / 2);
+    for row in board.rows() {
+        match board.get(row, center) {
+            Some(piece) if piece == player => score += 3,
+            Some(_) => score -= 3,
+            None => {}
+        }
+    }
+
+    let directions: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+    for row in 0..height {
+        for col in 0..width {
+            for (d_row, d_col) in directions {
+                let end_row = row + (connect - 1) * d_row;
+                let end_col = col + (connect - 1) * d_col;
+                if !(0..height).contains(&end_row) || (!wrap && !(0..width).contains(&end_col)) {
+                    continue;
+                }
+                let (mut mine, mut theirs, mut blocked) = (0, 0, false);
+                for i in 0..connect {
+                    let cell = board.cell(
+                        Row::new((row + i * d_row) as usize),
+                        Column::new((col + i * d_col).rem_euclid(width) as usize),
+                    );
+                    match cell {
+                        Cell::Piece(piece) if piece == player => mine += 1,
+                        Cell::Piece(_) => theirs += 1,
+                        Cell::Blocker => blocked = true,
+                        Cell::Empty => {}
+                    }
+                }
+                if !blocked {
+                    score += window_score(mine, theirs, connect);
+                }
+            }
+        }
+    }
+    if game.config().misere { -score } else { score }
+}
+
+// A window one piece short of a line is a threat, two short is worth a little
+fn window_score(mine: isize, theirs: isize, connect: isize) -> i32 {
+    match (mine, theirs) {
+        (0, 0) => 0,
+        (n, 0) if n == connect - 1 => 50,
+        (n, 0) if n == connect - 2 => 10,
+        (0, n) if n == connect - 1 => -50,
+        (0, n) if n == connect - 2 => -10,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GameConfig, Rules};
+
+    #[test]
+    fn test_opens_in_center() {
+        let game = Game::default();
+        let (col, _) = Negamax::new(1).best_move(&game).unwrap();
+        assert_eq!(col, Column::new(3));
+    }
+
+    #[test]
+    fn test_takes_immediate_win() {
+        // Player one has three in column one and it is their turn
+        let game = Game::from_notation("121212").unwrap();
+        let (col, score) = Negamax::new(4).best_move(&game).unwrap();
+        assert_eq!(col, Column::new(0));
+        assert_eq!(score, WIN_SCORE);
+    }
+
+    #[test]
+    fn test_blocks_opponent_win() {
+        // Player two has to stop three in a row in column one
+        let game = Game::from_notation("12131").unwrap();
+        let (col, _) = Negamax::new(4).best_move(&game).unwrap();
+        assert_eq!(col, Column::new(0));
+    }
+
+    #[test]
+    fn test_sees_forced_loss() {
+        // Player one has an open three along the bottom, so player two loses next move
+        let game = Game::from_notation("33445").unwrap();
+        let (_, score) = Negamax::new(4).best_move(&game).unwrap();
+        assert_eq!(score, -(WIN_SCORE - 1));
+    }
+
+    #[test]
+    fn test_no_move_when_game_over() {
+        let game = Game::from_notation("1212121").unwrap();
+        assert!(game.is_over());
+        assert_eq!(Negamax::default().best_move(&game), None);
+    }
+
+    #[test]
+    fn test_column_order_from_center() {
+        let order = |width| column_order(width).map(usize::from).collect::<Vec<_>>();
+        assert_eq!(order(7), [3, 2, 4, 1, 5, 0, 6]);
+        assert_eq!(order(8), [4, 3, 5, 2, 6, 1, 7, 0]);
+        assert_eq!(order(1), [0]);
+    }
+
+    #[test]
+    fn test_connect_three_on_small_board() {
+        // Player one has two along the bottom of a 5x4 board, open on both sides
+        let mut game = Game::new(GameConfig::new(5, 4, 3).unwrap());
+        for col in [1, 1, 2] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        let (_, score) = Negamax::new(3).best_move(&game).unwrap();
+        assert_eq!(score, -(WIN_SCORE - 1));
+    }
+
+    #[test]
+    fn test_blocks_win_around_cylinder() {
+        // Player one has the bottom of the last column and the first two, only the third
+        // column stops the line around the back
+        let mut game = Game::new(GameConfig::default().with_rules(Rules::Cylinder));
+        for col in [6, 5, 0, 5, 1] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        let (col, _) = Negamax::new(4).best_move(&game).unwrap();
+        assert_eq!(col, Column::new(2));
+    }
+
+    #[test]
+    fn test_avoids_line_in_misere() {
+        // Player one has three in column one, playing there again would lose
+        let mut game = Game::new(GameConfig::default().with_misere(true));
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        let (col, score) = Negamax::new(4).best_move(&game).unwrap();
+        assert_ne!(col, Column::new(0));
+        assert!(score > -WIN_SCORE / 2);
+    }
+}
@@ -0,0 +1,28 @@
+use std::{env, process};
+
+use connect_four_lib::{book::Book, game::Game, solver::Solver};
+
+// Writes an opening book covering every position with up to <depth> pieces on the board.
+// Each extra ply costs a lot more than the last, a depth of 8 already takes a while.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (Some(depth), Some(output)) = (args.get(1).and_then(|d| d.parse().ok()), args.get(2))
+    else {
+        eprintln!("usage: generate_book <depth> <output file>");
+        process::exit(1);
+    };
+
+    let mut solver = Solver::new();
+    let book = Book::generate(&Game::default(), depth, &mut solver);
+    if let Err(e) = book.save(output) {
+        eprintln!("failed to write {output}: {e}");
+        process::exit(1);
+    }
+    println!(
+        "wrote {} positions up to depth {} to {} ({} positions searched)",
+        book.len(),
+        depth,
+        output,
+        solver.explored()
+    );
+}
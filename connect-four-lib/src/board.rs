@@ -1,133 +1,467 @@
-use crate::player::Player;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-// Use array instead of vector since the board is a fixed size
-// Each slot in the grid will either have nothing or the `Player` type
-pub type BoardArray = [[Option<Player>; 7]; 6];
+use crate::{
+    config::MAX_CELL_BITS,
+    errors::GameError,
+    player::{MAX_PLAYERS, Player},
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
-pub struct Board(BoardArray);
+// Array form of the board, used when the whole grid has to be sent somewhere. Indexed by
+// row then column, with row 0 at the top.
+pub type BoardArray = Vec<Vec<Cell>>;
+
+// What a slot in the grid holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Cell {
+    #[default]
+    Empty,
+    // Neutral cell that belongs to nobody, pieces stack on top of it
+    Blocker,
+    Piece(Player),
+}
+
+// Pieces are stored as one bitboard per seat. Each column takes `height + 1` bits so the
+// spare bit on top stops lines from wrapping into the next column when the bitboards get
+// shifted. Bit `col * (height + 1) + n` is the nth slot from the bottom of `col`, so the
+// bottom row maps to the lowest bit of a column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    // Indexed by `Player::index`
+    pieces: [u128; MAX_PLAYERS],
+    // Neutral cells, which fill space but are never part of a line
+    blockers: u128,
+    // Whether the last column is joined to the first, for `Rules::Cylinder`
+    wrap_around: bool,
+}
 
 impl Default for Board {
     fn default() -> Self {
-        Self::new()
+        Self::new(7, 6)
     }
 }
 
 impl Board {
-    pub fn new() -> Self {
-        Board([[None; 7]; 6])
+    // Panics if the board doesn't fit in a bitboard, see `GameConfig::validate`
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            (height + 1) * width <= MAX_CELL_BITS,
+            "{width}x{height} board is too big"
+        );
+        Board {
+            width,
+            height,
+            pieces: [0; MAX_PLAYERS],
+            blockers: 0,
+            wrap_around: false,
+        }
+    }
+
+    pub fn with_wrap_around(self, wrap_around: bool) -> Self {
+        Board {
+            wrap_around,
+            ..self
+        }
+    }
+
+    pub fn wraps_around(&self) -> bool {
+        self.wrap_around
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Columns from left to right
+    pub fn columns(&self) -> impl DoubleEndedIterator<Item = Column> + use<> {
+        (0..self.width).map(Column::new)
+    }
+
+    // Rows from top to bottom
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = Row> + use<> {
+        (0..self.height).map(Row::new)
+    }
+
+    fn column_bits(&self) -> usize {
+        self.height + 1
+    }
+
+    fn bit(&self, row: Row, col: Column) -> Option<u128> {
+        let (row, col) = (usize::from(row), usize::from(col));
+        if row >= self.height || col >= self.width {
+            return None;
+        }
+        Some(1 << (col * self.column_bits() + (self.height - 1 - row)))
+    }
+
+    // Every occupied cell, blockers included
+    fn occupied(&self) -> u128 {
+        self.pieces
+            .iter()
+            .fold(self.blockers, |occupied, pieces| occupied | pieces)
+    }
+
+    // Every cell of `col`, without the spare bit on top
+    fn column_mask(&self, col: Column) -> u128 {
+        ((1 << self.height) - 1) << (usize::from(col) * self.column_bits())
     }
 
     pub fn get(&self, row: Row, col: Column) -> Option<Player> {
-        self.0[usize::from(row)][usize::from(col)]
+        let bit = self.bit(row, col)?;
+        let index = self.pieces.iter().position(|pieces| pieces & bit != 0)?;
+        Player::from_index(index)
+    }
+
+    pub fn cell(&self, row: Row, col: Column) -> Cell {
+        match self.get(row, col) {
+            Some(player) => Cell::Piece(player),
+            None if self.is_blocker(row, col) => Cell::Blocker,
+            None => Cell::Empty,
+        }
     }
 
+    pub fn is_blocker(&self, row: Row, col: Column) -> bool {
+        self.bit(row, col)
+            .is_some_and(|bit| self.blockers & bit != 0)
+    }
+
+    // Does nothing for cells outside the board
     pub fn insert_piece(&mut self, row: Row, col: Column, piece: Player) {
-        self.0[usize::from(row)][usize::from(col)] = Some(piece);
+        // Spectators never own a piece
+        let (Some(bit), Some(index)) = (self.bit(row, col), piece.index()) else {
+            return;
+        };
+        for pieces in &mut self.pieces {
+            *pieces &= !bit;
+        }
+        self.blockers &= !bit;
+        self.pieces[index] |= bit;
+    }
+
+    // Puts a blocker in the cell, replacing any piece there. Does nothing for cells outside
+    // the board.
+    pub fn place_blocker(&mut self, row: Row, col: Column) {
+        if let Some(bit) = self.bit(row, col) {
+            self.remove_piece(row, col);
+            self.blockers |= bit;
+        }
     }
 
+    pub fn remove_piece(&mut self, row: Row, col: Column) {
+        if let Some(bit) = self.bit(row, col) {
+            for pieces in &mut self.pieces {
+                *pieces &= !bit;
+            }
+        }
+    }
+
+    // Columns outside the board count as full
     pub fn is_slot_full(&self, col: &Column) -> bool {
-        let pieces = Row::iter()
-            .filter(|row| self.get(*row, *col).is_some())
-            .count();
+        match self.bit(Row::new(0), *col) {
+            Some(top) => self.occupied() & top != 0,
+            None => true,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.columns().all(|col| self.is_slot_full(&col))
+    }
+
+    // Removes the bottom piece of `col` and lets the rest of the column fall down a row,
+    // returning whose piece it was. Blockers in the column fall with it, and one at the
+    // bottom can't be popped.
+    pub fn pop_piece(&mut self, col: Column) -> Option<Player> {
+        let bottom = self.get(Row::new(self.height.checked_sub(1)?), col)?;
+        let column = self.column_mask(col);
+        for pieces in self.pieces.iter_mut().chain([&mut self.blockers]) {
+            *pieces = (*pieces & !column) | (((*pieces & column) >> 1) & column);
+        }
+        Some(bottom)
+    }
+
+    // Inverse of `Board::pop_piece`: pushes the column up a row and puts `piece` at the
+    // bottom. Does nothing if the column is full.
+    pub fn push_piece(&mut self, col: Column, piece: Player) {
+        if self.is_slot_full(&col) {
+            return;
+        }
+        let column = self.column_mask(col);
+        for pieces in self.pieces.iter_mut().chain([&mut self.blockers]) {
+            *pieces = (*pieces & !column) | (((*pieces & column) << 1) & column);
+        }
+        self.insert_piece(Row::new(self.height - 1), col, piece);
+    }
 
-        pieces == 6
+    // Row a piece dropped into `col` would land in, which is on top of whatever is highest
+    // in the column. Any empty cells under a blocker can't be reached.
+    pub fn drop_row(&self, col: Column) -> Option<Row> {
+        if usize::from(col) >= self.width {
+            return None;
+        }
+        match self.rows().find(|row| self.cell(*row, col) != Cell::Empty) {
+            Some(top) => usize::from(top).checked_sub(1).map(Row::new),
+            None => self.height.checked_sub(1).map(Row::new),
+        }
     }
 
     pub fn get_board_array(&self) -> BoardArray {
-        self.0
+        self.rows()
+            .map(|row| self.columns().map(|col| self.cell(row, col)).collect())
+            .collect()
+    }
+
+    // Returns the first player in turn order that has `connect` in a row, if any
+    pub fn winner(&self, connect: usize) -> Option<Player> {
+        Player::SEATS
+            .into_iter()
+            .find(|player| self.has_connected(*player, connect))
+    }
+
+    // Whether `player` has `connect` in a row. Popping a piece out can give several players
+    // a line at once, so sometimes everyone needs checking.
+    pub fn has_connected(&self, player: Player, connect: usize) -> bool {
+        player
+            .index()
+            .is_some_and(|index| self.has_line(self.pieces[index], connect))
+    }
+
+    // Whether the piece in the cell is part of `connect` in a row of its owner's pieces
+    pub fn in_line(&self, row: Row, col: Column, connect: usize) -> bool {
+        let Some(bit) = self.bit(row, col) else {
+            return false;
+        };
+        let Some(index) = self.get(row, col).and_then(Player::index) else {
+            return false;
+        };
+        let pieces = self.pieces[index];
+        let column_bits = self.column_bits();
+        [1, column_bits, column_bits + 1, column_bits - 1]
+            .into_iter()
+            .any(|shift| {
+                // Start of every line, then every cell covered by one
+                let mut starts = pieces;
+                for _ in 1..connect {
+                    starts &= starts >> shift;
+                }
+                let covered = (0..connect).fold(0, |covered, i| covered | starts << (i * shift));
+                covered & bit != 0
+            })
+    }
+
+    // Every line of `connect` of `player`'s pieces, each given as its cells column by column
+    // and bottom up. A row longer than `connect` counts as several overlapping lines.
+    pub fn lines(&self, player: Player, connect: usize) -> Vec<Vec<(Row, Column)>> {
+        let Some(index) = player.index() else {
+            return Vec::new();
+        };
+        let pieces = self.pieces[index];
+        let mut lines = self.straight_lines(pieces, connect);
+        for cols in self.seam_rotations(connect) {
+            // Found with the columns rotated as in `has_line`, then rotated back
+            let back = self.width - cols;
+            lines.extend(
+                self.straight_lines(self.rotate_columns(pieces, cols), connect)
+                    .into_iter()
+                    .map(|line| self.rotate_columns(line, back)),
+            );
+        }
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+            .into_iter()
+            .map(|line| {
+                self.columns()
+                    .flat_map(|col| self.rows().rev().map(move |row| (row, col)))
+                    .filter(|(row, col)| self.bit(*row, *col).is_some_and(|bit| line & bit != 0))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Each line found by `has_straight_line`, as a bitboard of its cells
+    fn straight_lines(&self, pieces: u128, connect: usize) -> Vec<u128> {
+        let column_bits = self.column_bits();
+        let mut lines = Vec::new();
+        for shift in [1, column_bits, column_bits + 1, column_bits - 1] {
+            let mut starts = pieces;
+            for _ in 1..connect {
+                starts &= starts >> shift;
+            }
+            while starts != 0 {
+                let start = starts & starts.wrapping_neg();
+                lines.push((0..connect).fold(0, |line, i| line | start << (i * shift)));
+                starts &= starts - 1;
+            }
+        }
+        lines
+    }
+
+    // Every cell on the board, leaving out the spare bit on top of each column
+    fn cells(&self) -> u128 {
+        let column = (1 << self.height) - 1;
+        (0..self.width).fold(0, |cells, col| cells | column << (col * self.column_bits()))
+    }
+
+    // Cells next to any of `pieces`, including diagonally
+    fn neighbours(&self, pieces: u128) -> u128 {
+        let around = |pieces: u128| {
+            let column_bits = self.column_bits();
+            [1, column_bits, column_bits + 1, column_bits - 1]
+                .into_iter()
+                .fold(0, |around, shift| {
+                    around | pieces << shift | pieces >> shift
+                })
+                & self.cells()
+        };
+        if self.wrap_around && self.width > 1 {
+            // Moving everything a column over puts the seam in the middle of the board
+            let rotated = around(self.rotate_columns(pieces, 1));
+            around(pieces) | self.rotate_columns(rotated, self.width - 1)
+        } else {
+            around(pieces)
+        }
+    }
+
+    // The board as `player` sees it in fog of war: their own pieces and anyone's pieces next
+    // to them. Spectators see everything.
+    pub fn visible_to(&self, player: Player) -> Board {
+        let Some(index) = player.index() else {
+            return *self;
+        };
+        let mine = self.pieces[index];
+        let shown = mine | self.neighbours(mine);
+        Board {
+            pieces: self.pieces.map(|pieces| pieces & shown),
+            ..*self
+        }
+    }
+
+    // Moves every column `cols` to the right, with the columns pushed off the right edge
+    // coming back in on the left
+    fn rotate_columns(&self, pieces: u128, cols: usize) -> u128 {
+        let total = self.width * self.column_bits();
+        let shift = (cols % self.width) * self.column_bits();
+        if shift == 0 {
+            return pieces;
+        }
+        let mask = if total == MAX_CELL_BITS {
+            u128::MAX
+        } else {
+            (1 << total) - 1
+        };
+        ((pieces << shift) | (pieces >> (total - shift))) & mask
+    }
+
+    // Shift-and-AND line detection: after `pieces & (pieces >> shift)` every set bit starts
+    // a pair in that direction, and repeating it finds longer lines. On a cylinder the
+    // columns are rotated so that lines across the seam are checked as well.
+    fn has_line(&self, pieces: u128, connect: usize) -> bool {
+        self.has_straight_line(pieces, connect)
+            || self
+                .seam_rotations(connect)
+                .any(|cols| self.has_straight_line(self.rotate_columns(pieces, cols), connect))
+    }
+
+    // Rotations that bring every line across the seam of a cylinder in one piece, one for
+    // each column of the line that can come after the seam
+    fn seam_rotations(&self, connect: usize) -> impl Iterator<Item = usize> + use<> {
+        let rotations = if self.wrap_around {
+            connect.min(self.width)
+        } else {
+            1
+        };
+        1..rotations
+    }
+
+    fn has_straight_line(&self, pieces: u128, connect: usize) -> bool {
+        let column_bits = self.column_bits();
+        // Vertical, horizontal, diagonal and anti-diagonal
+        [1, column_bits, column_bits + 1, column_bits - 1]
+            .into_iter()
+            .any(|shift| {
+                let mut line = pieces;
+                for _ in 1..connect {
+                    line &= line >> shift;
+                }
+                line != 0
+            })
     }
 }
 
-// Slots represent the columns that you drop pieces into
-#[derive(Debug, EnumIter, Clone, Copy, PartialEq, Eq)]
-pub enum Column {
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
+// Slots represent the columns that you drop pieces into, numbered from 0 on the left
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Column(usize);
+
+impl Column {
+    pub const fn new(index: usize) -> Self {
+        Column(index)
+    }
 }
 
 impl From<Column> for usize {
     fn from(value: Column) -> Self {
-        match value {
-            Column::One => 0,
-            Column::Two => 1,
-            Column::Three => 2,
-            Column::Four => 3,
-            Column::Five => 4,
-            Column::Six => 5,
-            Column::Seven => 6,
-        }
+        value.0
     }
 }
 
 impl From<&Column> for usize {
     fn from(value: &Column) -> Self {
-        match value {
-            Column::One => 0,
-            Column::Two => 1,
-            Column::Three => 2,
-            Column::Four => 3,
-            Column::Five => 4,
-            Column::Six => 5,
-            Column::Seven => 6,
-        }
+        value.0
     }
 }
 
-impl From<usize> for Column {
-    fn from(value: usize) -> Self {
-        match value {
-            0 => Column::One,
-            1 => Column::Two,
-            2 => Column::Three,
-            3 => Column::Four,
-            4 => Column::Five,
-            5 => Column::Six,
-            6 => Column::Seven,
-            _ => panic!("Column index out of bounds"),
+// Every column and row of a board has to fit in a bitboard along with its spare bit, which
+// caps the widest and tallest boards. Indices past them can't be on any board.
+pub const MAX_COLUMNS: usize = MAX_CELL_BITS / 2;
+pub const MAX_ROWS: usize = MAX_CELL_BITS - 1;
+
+// For indices from outside, such as a client's move. Whether the column is on this game's
+// board is still up to `Game`.
+impl TryFrom<usize> for Column {
+    type Error = GameError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value >= MAX_COLUMNS {
+            return Err(GameError::OutOfBounds(value));
         }
+        Ok(Column(value))
     }
 }
 
-#[derive(Debug, EnumIter, Clone, Copy, PartialEq, Eq)]
-pub enum Row {
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
+// Rows are numbered from 0 at the top
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Row(usize);
+
+impl Row {
+    pub const fn new(index: usize) -> Self {
+        Row(index)
+    }
 }
 
 impl From<Row> for usize {
     fn from(value: Row) -> Self {
-        match value {
-            Row::One => 0,
-            Row::Two => 1,
-            Row::Three => 2,
-            Row::Four => 3,
-            Row::Five => 4,
-            Row::Six => 5,
-        }
+        value.0
     }
 }
 
 impl From<&Row> for usize {
     fn from(value: &Row) -> Self {
-        match value {
-            Row::One => 0,
-            Row::Two => 1,
-            Row::Three => 2,
-            Row::Four => 3,
-            Row::Five => 4,
-            Row::Six => 5,
+        value.0
+    }
+}
+
+impl TryFrom<usize> for Row {
+    type Error = GameError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value >= MAX_ROWS {
+            return Err(GameError::OutOfBounds(value));
         }
+        Ok(Row(value))
     }
 }
 
@@ -136,9 +470,9 @@ mod tests {
     use super::*;
     #[test]
     fn test_board_new_empty() {
-        let board = Board::new();
-        for row in Row::iter() {
-            for col in Column::iter() {
+        let board = Board::default();
+        for row in board.rows() {
+            for col in board.columns() {
                 assert_eq!(board.get(row, col), None);
             }
         }
@@ -146,52 +480,413 @@ mod tests {
 
     #[test]
     fn test_insert_and_get_piece() {
-        let mut board = Board::new();
-        board.insert_piece(Row::Three, Column::Four, Player::One);
-        assert_eq!(board.get(Row::Three, Column::Four), Some(Player::One));
-        assert_eq!(board.get(Row::Two, Column::Four), None);
+        let mut board = Board::default();
+        board.insert_piece(Row::new(2), Column::new(3), Player::One);
+        assert_eq!(board.get(Row::new(2), Column::new(3)), Some(Player::One));
+        assert_eq!(board.get(Row::new(1), Column::new(3)), None);
+    }
+
+    #[test]
+    fn test_remove_piece() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(1), Player::Two);
+        board.remove_piece(Row::new(5), Column::new(1));
+        assert_eq!(board.get(Row::new(5), Column::new(1)), None);
+        assert_eq!(board, Board::default());
     }
 
     #[test]
     fn test_is_slot_full_false_and_true() {
-        let mut board = Board::new();
-        let col = Column::Three;
+        let mut board = Board::default();
+        let col = Column::new(2);
         // Initially false
         assert!(!board.is_slot_full(&col));
         // Fill entire column
-        for row in Row::iter() {
+        for row in board.rows() {
             board.insert_piece(row, col, Player::Two);
         }
         assert!(board.is_slot_full(&col));
     }
 
+    #[test]
+    fn test_is_full() {
+        let mut board = Board::default();
+        for col in board.columns() {
+            assert!(!board.is_full());
+            for row in board.rows() {
+                board.insert_piece(row, col, Player::One);
+            }
+        }
+        assert!(board.is_full());
+    }
+
+    #[test]
+    fn test_insert_piece_replaces_other_player() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(0), Player::One);
+        board.insert_piece(Row::new(5), Column::new(0), Player::Two);
+        assert_eq!(board.get(Row::new(5), Column::new(0)), Some(Player::Two));
+    }
+
+    #[test]
+    fn test_board_array_orientation() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(6), Player::One);
+        board.insert_piece(Row::new(0), Column::new(0), Player::Two);
+        let array = board.get_board_array();
+        assert_eq!(array.len(), 6);
+        assert_eq!(array[0].len(), 7);
+        assert_eq!(array[5][6], Cell::Piece(Player::One));
+        assert_eq!(array[0][0], Cell::Piece(Player::Two));
+        assert_eq!(array[3][3], Cell::Empty);
+    }
+
+    #[test]
+    fn test_no_wrap_between_columns() {
+        let mut board = Board::default();
+        // Top of column one and bottom three of column two are consecutive bits
+        board.insert_piece(Row::new(0), Column::new(0), Player::One);
+        for row in [5, 4, 3] {
+            board.insert_piece(Row::new(row), Column::new(1), Player::One);
+        }
+        assert_eq!(board.winner(4), None);
+    }
+
+    #[test]
+    fn test_out_of_bounds_cells() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(6), Column::new(0), Player::One);
+        board.insert_piece(Row::new(0), Column::new(7), Player::One);
+        assert_eq!(board, Board::default());
+        assert_eq!(board.get(Row::new(6), Column::new(0)), None);
+        assert!(board.is_slot_full(&Column::new(7)));
+        assert_eq!(board.drop_row(Column::new(7)), None);
+    }
+
+    #[test]
+    fn test_drop_row() {
+        let mut board = Board::new(5, 4);
+        assert_eq!(board.drop_row(Column::new(4)), Some(Row::new(3)));
+        board.insert_piece(Row::new(3), Column::new(4), Player::One);
+        assert_eq!(board.drop_row(Column::new(4)), Some(Row::new(2)));
+    }
+
+    #[test]
+    fn test_winner_on_large_board() {
+        let mut board = Board::new(9, 7);
+        for col in 4..9 {
+            board.insert_piece(Row::new(6), Column::new(col), Player::Two);
+        }
+        assert_eq!(board.winner(5), Some(Player::Two));
+        assert_eq!(board.winner(6), None);
+        assert_eq!(board.get_board_array().len(), 7);
+        assert_eq!(board.get_board_array()[0].len(), 9);
+    }
+
+    #[test]
+    fn test_winner_connect_three() {
+        let mut board = Board::new(5, 4);
+        for i in 0..3 {
+            board.insert_piece(Row::new(3 - i), Column::new(2 + i), Player::One);
+        }
+        assert_eq!(board.winner(3), Some(Player::One));
+        assert_eq!(board.winner(4), None);
+    }
+
     #[test]
     fn test_column_from_usize() {
-        assert_eq!(usize::from(Column::One), 0);
-        assert_eq!(usize::from(Column::Four), 3);
-        assert_eq!(usize::from(Column::Seven), 6);
+        assert_eq!(usize::from(Column::new(0)), 0);
+        assert_eq!(usize::from(Column::new(3)), 3);
+        assert_eq!(Column::try_from(6).unwrap(), Column::new(6));
+        assert!(matches!(
+            Column::try_from(MAX_COLUMNS),
+            Err(GameError::OutOfBounds(64))
+        ));
+        assert!(matches!(
+            Column::try_from(usize::MAX),
+            Err(GameError::OutOfBounds(_))
+        ));
     }
 
     #[test]
     fn test_column_ref_from_usize() {
-        let col = Column::Three;
+        let col = Column::new(2);
         assert_eq!(usize::from(&col), 2);
-        let col = Column::Five;
+        let col = Column::new(4);
         assert_eq!(usize::from(&col), 4);
     }
 
     #[test]
     fn test_row_from_usize() {
-        assert_eq!(usize::from(Row::One), 0);
-        assert_eq!(usize::from(Row::Six), 5);
-        assert_eq!(usize::from(Row::Four), 3);
+        assert_eq!(usize::from(Row::new(0)), 0);
+        assert_eq!(usize::from(Row::new(5)), 5);
+        assert_eq!(usize::from(Row::new(3)), 3);
+    }
+
+    #[test]
+    fn test_row_try_from_usize() {
+        assert_eq!(Row::try_from(5).unwrap(), Row::new(5));
+        assert!(matches!(
+            Row::try_from(MAX_ROWS),
+            Err(GameError::OutOfBounds(127))
+        ));
     }
 
     #[test]
     fn test_row_ref_from_usize() {
-        let row = Row::Two;
+        let row = Row::new(1);
         assert_eq!(usize::from(&row), 1);
-        let row = Row::Five;
+        let row = Row::new(4);
         assert_eq!(usize::from(&row), 4);
     }
+
+    #[test]
+    fn test_pop_piece_shifts_column_down() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(2), Player::One);
+        board.insert_piece(Row::new(4), Column::new(2), Player::Two);
+        board.insert_piece(Row::new(3), Column::new(2), Player::One);
+        board.insert_piece(Row::new(5), Column::new(3), Player::Two);
+        assert_eq!(board.pop_piece(Column::new(2)), Some(Player::One));
+        assert_eq!(board.get(Row::new(5), Column::new(2)), Some(Player::Two));
+        assert_eq!(board.get(Row::new(4), Column::new(2)), Some(Player::One));
+        assert_eq!(board.get(Row::new(3), Column::new(2)), None);
+        // Other columns are left alone
+        assert_eq!(board.get(Row::new(5), Column::new(3)), Some(Player::Two));
+        assert_eq!(board.pop_piece(Column::new(4)), None);
+    }
+
+    #[test]
+    fn test_push_piece_undoes_pop() {
+        let mut board = Board::new(5, 4);
+        for (row, piece) in [(3, Player::Two), (2, Player::One), (1, Player::One)] {
+            board.insert_piece(Row::new(row), Column::new(4), piece);
+        }
+        let before = board;
+        let popped = board.pop_piece(Column::new(4)).unwrap();
+        board.push_piece(Column::new(4), popped);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn test_has_connected_both_players() {
+        let mut board = Board::default();
+        for col in 0..4 {
+            board.insert_piece(Row::new(5), Column::new(col), Player::One);
+            board.insert_piece(Row::new(4), Column::new(col), Player::Two);
+        }
+        assert!(board.has_connected(Player::One, 4));
+        assert!(board.has_connected(Player::Two, 4));
+        assert!(!board.has_connected(Player::Spectator, 4));
+    }
+
+    #[test]
+    fn test_in_line() {
+        let mut board = Board::default();
+        for row in [5, 4, 3, 2] {
+            board.insert_piece(Row::new(row), Column::new(1), Player::Two);
+        }
+        board.insert_piece(Row::new(5), Column::new(2), Player::Two);
+        board.insert_piece(Row::new(5), Column::new(0), Player::One);
+        assert!(board.in_line(Row::new(5), Column::new(1), 4));
+        assert!(board.in_line(Row::new(2), Column::new(1), 4));
+        assert!(!board.in_line(Row::new(5), Column::new(2), 4));
+        assert!(!board.in_line(Row::new(5), Column::new(0), 4));
+        assert!(!board.in_line(Row::new(1), Column::new(1), 4));
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let mut board = Board::default();
+        for col in [5, 6, 0, 1] {
+            board.insert_piece(Row::new(5), Column::new(col), Player::One);
+        }
+        assert!(!board.has_connected(Player::One, 4));
+        assert!(board.with_wrap_around(true).has_connected(Player::One, 4));
+    }
+
+    #[test]
+    fn test_wrap_around_narrow_cylinders() {
+        // Lines that cross the seam at any point, on boards barely wider than a line
+        for (width, connect, cols) in [
+            (5, 4, vec![4, 0, 1, 2]),
+            (5, 4, vec![3, 4, 0, 1]),
+            (7, 5, vec![6, 0, 1, 2, 3]),
+            (7, 5, vec![4, 5, 6, 0, 1]),
+            (4, 4, vec![2, 3, 0, 1]),
+        ] {
+            let mut board = Board::new(width, 6).with_wrap_around(true);
+            for col in &cols {
+                board.insert_piece(Row::new(5), Column::new(*col), Player::One);
+            }
+            assert!(board.has_connected(Player::One, connect), "{cols:?}");
+            let mut sorted = cols.clone();
+            sorted.sort();
+            assert_eq!(
+                board.lines(Player::One, connect),
+                vec![
+                    sorted
+                        .into_iter()
+                        .map(|col| (Row::new(5), Column::new(col)))
+                        .collect::<Vec<_>>()
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_around_diagonal() {
+        let mut board = Board::new(16, 7).with_wrap_around(true);
+        for (row, col) in [(6, 14), (5, 15), (4, 0), (3, 1)] {
+            board.insert_piece(Row::new(row), Column::new(col), Player::Two);
+        }
+        assert_eq!(board.winner(4), Some(Player::Two));
+        // The seam doesn't join the top of one column to the bottom of the next
+        let mut board = Board::default().with_wrap_around(true);
+        board.insert_piece(Row::new(0), Column::new(6), Player::One);
+        for row in [5, 4, 3] {
+            board.insert_piece(Row::new(row), Column::new(0), Player::One);
+        }
+        assert!(!board.has_connected(Player::One, 4));
+    }
+
+    #[test]
+    fn test_visible_to() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(3), Player::One);
+        board.insert_piece(Row::new(4), Column::new(4), Player::Two);
+        board.insert_piece(Row::new(5), Column::new(5), Player::Two);
+        board.insert_piece(Row::new(5), Column::new(0), Player::Two);
+        let view = board.visible_to(Player::One);
+        assert_eq!(view.get(Row::new(5), Column::new(3)), Some(Player::One));
+        assert_eq!(view.get(Row::new(4), Column::new(4)), Some(Player::Two));
+        assert_eq!(view.get(Row::new(5), Column::new(5)), None);
+        assert_eq!(view.get(Row::new(5), Column::new(0)), None);
+        assert_eq!(board.visible_to(Player::Spectator), board);
+    }
+
+    #[test]
+    fn test_visible_around_cylinder() {
+        let mut board = Board::default();
+        board.insert_piece(Row::new(5), Column::new(0), Player::One);
+        board.insert_piece(Row::new(4), Column::new(6), Player::Two);
+        // The top of one column doesn't touch the bottom of the next
+        board.insert_piece(Row::new(0), Column::new(6), Player::Two);
+        assert_eq!(
+            board
+                .visible_to(Player::One)
+                .get(Row::new(4), Column::new(6)),
+            None
+        );
+        let view = board.with_wrap_around(true).visible_to(Player::One);
+        assert_eq!(view.get(Row::new(4), Column::new(6)), Some(Player::Two));
+        assert_eq!(view.get(Row::new(0), Column::new(6)), None);
+    }
+
+    #[test]
+    fn test_four_players() {
+        let mut board = Board::new(10, 8);
+        for (col, player) in Player::SEATS.into_iter().enumerate() {
+            board.insert_piece(Row::new(7), Column::new(col), player);
+        }
+        for (col, player) in Player::SEATS.into_iter().enumerate() {
+            assert_eq!(board.get(Row::new(7), Column::new(col)), Some(player));
+        }
+        assert_eq!(board.winner(4), None);
+        for row in [6, 5, 4] {
+            board.insert_piece(Row::new(row), Column::new(3), Player::Four);
+        }
+        assert_eq!(board.winner(4), Some(Player::Four));
+        assert!(!board.has_connected(Player::Three, 4));
+    }
+
+    #[test]
+    fn test_drops_land_on_blockers() {
+        let mut board = Board::new(5, 4);
+        board.place_blocker(Row::new(3), Column::new(0));
+        board.place_blocker(Row::new(1), Column::new(1));
+        assert_eq!(board.drop_row(Column::new(0)), Some(Row::new(2)));
+        // Floating blockers leave the cells under them empty for good
+        assert_eq!(board.drop_row(Column::new(1)), Some(Row::new(0)));
+        assert_eq!(board.cell(Row::new(1), Column::new(1)), Cell::Blocker);
+        assert_eq!(board.cell(Row::new(2), Column::new(1)), Cell::Empty);
+        assert_eq!(board.get(Row::new(1), Column::new(1)), None);
+        board.insert_piece(Row::new(0), Column::new(1), Player::One);
+        assert!(board.is_slot_full(&Column::new(1)));
+        assert_eq!(board.drop_row(Column::new(1)), None);
+        assert_eq!(board.get_board_array()[1][1], Cell::Blocker);
+    }
+
+    #[test]
+    fn test_blockers_never_make_a_line() {
+        let mut board = Board::default();
+        for col in 0..3 {
+            board.insert_piece(Row::new(5), Column::new(col), Player::One);
+        }
+        board.place_blocker(Row::new(5), Column::new(3));
+        assert_eq!(board.winner(4), None);
+        assert_eq!(board.winner(3), Some(Player::One));
+        // Blockers are seen through fog of war
+        assert!(
+            board
+                .visible_to(Player::Two)
+                .is_blocker(Row::new(5), Column::new(3))
+        );
+    }
+
+    #[test]
+    fn test_pop_moves_blockers_down() {
+        let mut board = Board::new(5, 4);
+        board.insert_piece(Row::new(3), Column::new(2), Player::Two);
+        board.place_blocker(Row::new(2), Column::new(2));
+        let before = board;
+        assert_eq!(board.pop_piece(Column::new(2)), Some(Player::Two));
+        assert!(board.is_blocker(Row::new(3), Column::new(2)));
+        // A blocker at the bottom stays put
+        assert_eq!(board.pop_piece(Column::new(2)), None);
+        board.push_piece(Column::new(2), Player::Two);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut board = Board::default();
+        for row in [5, 4, 3, 2] {
+            board.insert_piece(Row::new(row), Column::new(1), Player::Two);
+        }
+        for col in [1, 2, 3, 4, 5] {
+            board.insert_piece(Row::new(0), Column::new(col), Player::Two);
+        }
+        let lines = board.lines(Player::Two, 4);
+        assert_eq!(lines.len(), 3);
+        assert!(lines.contains(&vec![
+            (Row::new(5), Column::new(1)),
+            (Row::new(4), Column::new(1)),
+            (Row::new(3), Column::new(1)),
+            (Row::new(2), Column::new(1)),
+        ]));
+        assert!(lines.contains(&(2..6).map(|col| (Row::new(0), Column::new(col))).collect()));
+        assert!(board.lines(Player::One, 4).is_empty());
+        assert!(board.lines(Player::Spectator, 4).is_empty());
+    }
+
+    #[test]
+    fn test_lines_around_cylinder() {
+        let mut board = Board::default().with_wrap_around(true);
+        for col in [5, 6, 0, 1] {
+            board.insert_piece(Row::new(5), Column::new(col), Player::One);
+        }
+        let lines = board.lines(Player::One, 4);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0],
+            [0, 1, 5, 6].map(|col| (Row::new(5), Column::new(col)))
+        );
+        assert!(
+            board
+                .with_wrap_around(false)
+                .lines(Player::One, 4)
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,214 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    game::Game,
+    solver::{Position, Solver},
+};
+
+const MAGIC: &[u8; 4] = b"C4BK";
+const VERSION: u8 = 1;
+// Magic, version, depth and entry count
+const HEADER_LEN: usize = 4 + 1 + 1 + 4;
+
+// Precomputed solver scores for every position up to a number of moves. Mirrored
+// positions always have the same score so only one of each pair is stored.
+//
+// On disk the book is a small header followed by the entries sorted by key, each one a
+// little endian u64 holding the 49 bit position key above an 8 bit score.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Book {
+    depth: u8,
+    entries: Vec<u64>,
+}
+
+impl Book {
+    // Solves every position reachable from `root` that has at most `depth` pieces on the
+    // board. Starting from a new game this covers the whole opening.
+    pub fn generate(root: &Game, depth: u8, solver: &mut Solver) -> Self {
+        let mut scores = HashMap::new();
+        if let Some(position) = Position::from_game(root) {
+            explore(&position, i32::from(depth), solver, &mut scores);
+        }
+        let mut entries: Vec<u64> = scores
+            .into_iter()
+            .map(|(key, score)| (key << 8) | u64::from(score as i8 as u8))
+            .collect();
+        entries.sort_unstable();
+        Book { depth, entries }
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Score of the position for the player to move, as returned by the solver
+    pub fn get(&self, game: &Game) -> Option<i32> {
+        self.get_position(&Position::from_game(game)?)
+    }
+
+    pub(crate) fn get_position(&self, position: &Position) -> Option<i32> {
+        if position.moves() > i32::from(self.depth) {
+            return None;
+        }
+        let key = position.canonical_key();
+        let index = self
+            .entries
+            .binary_search_by_key(&key, |entry| entry >> 8)
+            .ok()?;
+        Some(i32::from(self.entries[index] as u8 as i8))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.entries.len() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.depth);
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_owned());
+
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(invalid("not an opening book"));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid("unsupported opening book version"));
+        }
+        let depth = bytes[5];
+        let count = u32::from_le_bytes(bytes[6..HEADER_LEN].try_into().unwrap()) as usize;
+        let body = &bytes[HEADER_LEN..];
+        if body.len() != count * 8 {
+            return Err(invalid("opening book is truncated"));
+        }
+        let entries: Vec<u64> = body
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        if !entries.is_sorted() {
+            return Err(invalid("opening book entries are out of order"));
+        }
+        Ok(Book { depth, entries })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+fn explore(position: &Position, depth: i32, solver: &mut Solver, scores: &mut HashMap<u64, i32>) {
+    let key = position.canonical_key();
+    if scores.contains_key(&key) {
+        return;
+    }
+    scores.insert(key, solver.solve_position(position));
+    if position.moves() >= depth {
+        return;
+    }
+    for col in 0..7 {
+        // Winning moves end the game, so there is nothing to store past them
+        if position.can_play(col) && !position.is_winning_move(col) {
+            let mut child = *position;
+            child.play(child.column_move(col));
+            explore(&child, depth, solver, scores);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Late enough in the game that the whole tree solves instantly
+    const ROOT: &str = "326232661414515115535347513434";
+
+    fn mirror(moves: &str) -> String {
+        moves
+            .chars()
+            .map(|c| char::from_digit(8 - c.to_digit(10).unwrap(), 10).unwrap())
+            .collect()
+    }
+
+    fn small_book() -> Book {
+        let root = Game::from_notation(ROOT).unwrap();
+        Book::generate(
+            &root,
+            ROOT.len() as u8 + 2,
+            &mut Solver::with_table_size(1 << 16),
+        )
+    }
+
+    #[test]
+    fn test_lookup_matches_solver() {
+        let book = small_book();
+        assert!(!book.is_empty());
+        let mut solver = Solver::with_table_size(1 << 16);
+        for moves in [ROOT.to_owned(), format!("{ROOT}2"), format!("{ROOT}76")] {
+            let game = Game::from_notation(&moves).unwrap();
+            assert_eq!(book.get(&game), Some(solver.solve(&game).unwrap().score));
+        }
+    }
+
+    #[test]
+    fn test_lookup_folds_mirrored_positions() {
+        let book = small_book();
+        for moves in [ROOT.to_owned(), format!("{ROOT}6"), format!("{ROOT}46")] {
+            let game = Game::from_notation(&moves).unwrap();
+            let mirrored = Game::from_notation(&mirror(&moves)).unwrap();
+            assert!(book.get(&game).is_some());
+            assert_eq!(book.get(&game), book.get(&mirrored));
+        }
+    }
+
+    #[test]
+    fn test_lookup_past_depth() {
+        let book = small_book();
+        let game = Game::from_notation(&format!("{ROOT}267")).unwrap();
+        assert_eq!(book.get(&game), None);
+        assert_eq!(book.get(&Game::default()), None);
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let book = small_book();
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + book.len() * 8);
+        assert_eq!(Book::from_bytes(&bytes).unwrap(), book);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_bad_data() {
+        let bytes = small_book().to_bytes();
+        assert!(Book::from_bytes(b"nope").is_err());
+        assert!(Book::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = VERSION + 1;
+        assert!(Book::from_bytes(&wrong_version).is_err());
+    }
+
+    #[test]
+    fn test_solver_uses_book() {
+        let mut solver = Solver::with_table_size(1 << 16);
+        solver.set_book(small_book());
+        let game = Game::from_notation(ROOT).unwrap();
+        let expected = Solver::with_table_size(1 << 16).solve(&game).unwrap();
+        assert_eq!(solver.solve(&game), Some(expected));
+        assert_eq!(solver.explored(), 0);
+    }
+}
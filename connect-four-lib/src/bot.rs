@@ -0,0 +1,257 @@
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::{
+    ai::{Agent, Negamax, check_supported},
+    board::Column,
+    book::Book,
+    config::GameConfig,
+    errors::GameError,
+    game::Game,
+    mcts::{Budget, Mcts},
+    solver::{DEFAULT_TABLE_SIZE, Solver},
+    threats::{must_block, winning_moves},
+};
+
+// Before this many moves the solver takes too long without an opening book, so perfect
+// bots search instead
+const SOLVER_FROM_MOVE: usize = 12;
+
+// Playouts a fuzzy bot runs per move, which keeps its play sound but not sharp
+const FUZZY_ITERATIONS: u32 = 2000;
+
+// Named strength presets for a computer opponent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum Difficulty {
+    Beginner,
+    Casual,
+    // Plays by Monte Carlo tree search, which makes the odd human-looking mistake
+    Fuzzy,
+    Strong,
+    // Solves positions exactly from move `SOLVER_FROM_MOVE` on, or earlier where its opening
+    // book has them, and searches nine moves ahead before that
+    Perfect,
+}
+
+impl Difficulty {
+    // How many moves ahead the bot searches
+    pub fn search_depth(self) -> u32 {
+        match self {
+            Difficulty::Beginner => 1,
+            Difficulty::Casual | Difficulty::Fuzzy => 3,
+            Difficulty::Strong => 7,
+            Difficulty::Perfect => 9,
+        }
+    }
+
+    // Chance of playing a random move instead of the one the search picked
+    pub fn blunder_chance(self) -> f64 {
+        match self {
+            Difficulty::Beginner => 0.3,
+            Difficulty::Casual => 0.1,
+            Difficulty::Fuzzy | Difficulty::Strong | Difficulty::Perfect => 0.0,
+        }
+    }
+
+    // Whether the bot always takes a win and blocks the opponent's, even on a blunder.
+    // Fuzzy bots leave that to their playouts.
+    pub fn sees_threats(self) -> bool {
+        !matches!(self, Difficulty::Beginner | Difficulty::Fuzzy)
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Beginner => write!(f, "Beginner"),
+            Difficulty::Casual => write!(f, "Casual"),
+            Difficulty::Fuzzy => write!(f, "Fuzzy"),
+            Difficulty::Strong => write!(f, "Strong"),
+            Difficulty::Perfect => write!(f, "Perfect"),
+        }
+    }
+}
+
+// Computer opponent playing at one of the `Difficulty` presets
+pub struct Bot {
+    difficulty: Difficulty,
+    negamax: Negamax,
+    solver: Option<Solver>,
+    mcts: Option<Mcts>,
+    rng: StdRng,
+}
+
+impl Bot {
+    // Bot for games played with `config`, or an error if the engines can't play them
+    pub fn new(difficulty: Difficulty, config: &GameConfig) -> Result<Self, GameError> {
+        Self::with_rng(difficulty, config, StdRng::from_entropy())
+    }
+
+    pub fn with_seed(
+        difficulty: Difficulty,
+        config: &GameConfig,
+        seed: u64,
+    ) -> Result<Self, GameError> {
+        Self::with_rng(difficulty, config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(
+        difficulty: Difficulty,
+        config: &GameConfig,
+        mut rng: StdRng,
+    ) -> Result<Self, GameError> {
+        check_supported(config)?;
+        Ok(Bot {
+            difficulty,
+            negamax: Negamax::new(difficulty.search_depth()),
+            solver: (difficulty == Difficulty::Perfect)
+                .then(|| Solver::with_table_size(DEFAULT_TABLE_SIZE)),
+            mcts: (difficulty == Difficulty::Fuzzy)
+                .then(|| Mcts::with_seed(Budget::Iterations(FUZZY_ITERATIONS), rng.r#gen())),
+            rng,
+        })
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    // Lets a perfect bot solve the opening too. Other difficulties ignore it.
+    pub fn set_book(&mut self, book: Book) {
+        if let Some(solver) = &mut self.solver {
+            solver.set_book(book);
+        }
+    }
+}
+
+impl Agent for Bot {
+    fn choose_move(&mut self, game: &Game) -> Option<Column> {
+        if game.is_over() {
+            return None;
+        }
+        if self.difficulty.sees_threats()
+            && let Some(col) = winning_moves(game, game.current_player())
+                .first()
+                .copied()
+                .or_else(|| must_block(game).first().copied())
+        {
+            return Some(col);
+        }
+        if let Some(mcts) = &mut self.mcts {
+            return mcts.choose_move(game);
+        }
+        if self.rng.gen_bool(self.difficulty.blunder_chance()) {
+            let board = game.get_board();
+            let moves: Vec<Column> = board
+                .columns()
+                .filter(|col| !board.is_slot_full(col))
+                .collect();
+            return moves.choose(&mut self.rng).copied();
+        }
+        // The solver only knows the standard board, so other configs always use negamax
+        if let Some(solver) = &mut self.solver
+            && (game.history().len() >= SOLVER_FROM_MOVE
+                || solver.book().is_some_and(|book| book.get(game).is_some()))
+            && let Some(col) = solver.choose_move(game)
+        {
+            return Some(col);
+        }
+        self.negamax.choose_move(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rules;
+    use strum::IntoEnumIterator;
+
+    fn bot(difficulty: Difficulty, seed: u64) -> Bot {
+        Bot::with_seed(difficulty, &GameConfig::default(), seed).unwrap()
+    }
+
+    #[test]
+    fn test_difficulty_serializes_by_name() {
+        let json = serde_json::to_string(&Difficulty::Casual).unwrap();
+        assert_eq!(json, "\"Casual\"");
+        let difficulty: Difficulty = serde_json::from_str("\"Perfect\"").unwrap();
+        assert_eq!(difficulty, Difficulty::Perfect);
+    }
+
+    #[test]
+    fn test_threat_aware_bots_block() {
+        // Player two has to stop three in a row in column one
+        let game = Game::from_notation("12131").unwrap();
+        for difficulty in [Difficulty::Casual, Difficulty::Strong] {
+            for seed in 0..10 {
+                let mut bot = bot(difficulty, seed);
+                assert_eq!(bot.choose_move(&game), Some(Column::new(0)), "{difficulty}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_beginner_sometimes_misses_threats() {
+        let game = Game::from_notation("12131").unwrap();
+        let missed = (0..50)
+            .filter(|seed| {
+                bot(Difficulty::Beginner, *seed).choose_move(&game) != Some(Column::new(0))
+            })
+            .count();
+        assert!(missed > 0);
+    }
+
+    #[test]
+    fn test_perfect_bot_solves_late_positions() {
+        let game = Game::from_notation("326232661414515115535347513434").unwrap();
+        let mut solver = Solver::with_table_size(1 << 16);
+        let (_, best) = solver.best_move(&game).unwrap();
+
+        let col = bot(Difficulty::Perfect, 0).choose_move(&game).unwrap();
+        let mut after = game.clone();
+        after.make_move(after.current_player(), &col).unwrap();
+        let score = match solver.solve(&after) {
+            Some(solution) => -solution.score,
+            None => best.score,
+        };
+        assert_eq!(score, best.score);
+    }
+
+    #[test]
+    fn test_seeded_bots_repeat() {
+        let game = Game::from_notation("3262326614145151155353475").unwrap();
+        for difficulty in Difficulty::iter() {
+            let first = bot(difficulty, 3).choose_move(&game);
+            let second = bot(difficulty, 3).choose_move(&game);
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_bot_searches_playouts() {
+        let game = Game::from_notation("121212").unwrap();
+        assert_eq!(
+            bot(Difficulty::Fuzzy, 1).choose_move(&game),
+            Some(Column::new(0))
+        );
+    }
+
+    #[test]
+    fn test_refuses_games_it_cant_play() {
+        let configs = [
+            GameConfig::default().with_rules(Rules::PopOut),
+            GameConfig::default().with_rules(Rules::Pop10),
+            GameConfig::for_players(3).unwrap(),
+            GameConfig::default().with_fog_of_war(true),
+        ];
+        for config in configs {
+            assert!(matches!(
+                Bot::new(Difficulty::Casual, &config),
+                Err(GameError::UnsupportedByBot(_))
+            ));
+        }
+        let config = GameConfig::default().with_rules(Rules::Cylinder);
+        assert!(Bot::new(Difficulty::Casual, &config).is_ok());
+    }
+}
@@ -0,0 +1,345 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::GameError, player::MAX_PLAYERS};
+
+// Pieces are stored in u128 bitboards with a spare bit on top of every column
+pub const MAX_CELL_BITS: usize = 128;
+
+// Which moves are allowed on top of dropping a piece
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rules {
+    #[default]
+    Standard,
+    // Players may pop one of their own pieces out of the bottom row instead of dropping
+    PopOut,
+    // The board is filled up, then players pop their pieces that are part of a line to
+    // collect them. The first to collect ten wins.
+    Pop10,
+    // The left and right edges are joined, so horizontal and diagonal lines can wrap around
+    Cylinder,
+}
+
+impl std::fmt::Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rules::Standard => write!(f, "Standard"),
+            Rules::PopOut => write!(f, "PopOut"),
+            Rules::Pop10 => write!(f, "Pop 10"),
+            Rules::Cylinder => write!(f, "Cylinder"),
+        }
+    }
+}
+
+// Neutral cells put on the board before the first move
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blockers {
+    #[default]
+    None,
+    // Bit `row * width + col` is set for every blocker, with row 0 at the top
+    Fixed(u128),
+    // `count` blockers dropped into columns picked by a generator seeded with `seed`, so
+    // every game with the same config starts from the same board
+    Random {
+        count: usize,
+        seed: u64,
+    },
+}
+
+impl Blockers {
+    // Blockers in the given (row, col) cells of a board `width` columns wide
+    pub fn fixed(
+        width: usize,
+        cells: impl IntoIterator<Item = (usize, usize)>,
+    ) -> Result<Self, GameError> {
+        let mut mask = 0u128;
+        for (row, col) in cells {
+            let bit = row
+                .checked_mul(width)
+                .and_then(|start| start.checked_add(col))
+                .and_then(|index| u32::try_from(index).ok())
+                .and_then(|index| 1u128.checked_shl(index))
+                .filter(|_| col < width)
+                .ok_or_else(|| {
+                    GameError::InvalidConfig(format!("blocker ({row}, {col}) is outside the board"))
+                })?;
+            mask |= bit;
+        }
+        Ok(Blockers::Fixed(mask))
+    }
+}
+
+// Size of the board, how many pieces in a row win and which rules are played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub width: usize,
+    pub height: usize,
+    pub connect: usize,
+    #[serde(default)]
+    pub rules: Rules,
+    // How many players take turns, between two and four
+    #[serde(default = "default_players")]
+    pub players: usize,
+    // Players only see their own pieces and the pieces touching them until the game ends
+    #[serde(default)]
+    pub fog_of_war: bool,
+    // Misère: making a line loses instead of winning
+    #[serde(default)]
+    pub misere: bool,
+    #[serde(default)]
+    pub blockers: Blockers,
+}
+
+fn default_players() -> usize {
+    2
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 7,
+            height: 6,
+            connect: 4,
+            rules: Rules::Standard,
+            players: default_players(),
+            fog_of_war: false,
+            misere: false,
+            blockers: Blockers::None,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new(width: usize, height: usize, connect: usize) -> Result<Self, GameError> {
+        let config = GameConfig {
+            width,
+            height,
+            connect,
+            rules: Rules::Standard,
+            players: default_players(),
+            fog_of_war: false,
+            misere: false,
+            blockers: Blockers::None,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Recommended board for the number of players, a bit bigger for every extra player
+    pub fn for_players(players: usize) -> Result<Self, GameError> {
+        let (width, height) = match players {
+            3 => (9, 7),
+            4 => (10, 8),
+            _ => (7, 6),
+        };
+        GameConfig::new(width, height, 4)?.with_players(players)
+    }
+
+    pub fn with_rules(self, rules: Rules) -> Self {
+        GameConfig { rules, ..self }
+    }
+
+    pub fn with_fog_of_war(self, fog_of_war: bool) -> Self {
+        GameConfig { fog_of_war, ..self }
+    }
+
+    pub fn with_misere(self, misere: bool) -> Self {
+        GameConfig { misere, ..self }
+    }
+
+    pub fn with_blockers(self, blockers: Blockers) -> Self {
+        GameConfig { blockers, ..self }
+    }
+
+    pub fn with_players(self, players: usize) -> Result<Self, GameError> {
+        let config = GameConfig { players, ..self };
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Checks that the board fits in a bitboard and that a line can actually be made
+    pub fn validate(&self) -> Result<(), GameError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(GameError::InvalidConfig(
+                "the board needs at least one cell".to_owned(),
+            ));
+        }
+        if (self.height + 1) * self.width > MAX_CELL_BITS {
+            return Err(GameError::InvalidConfig("the board is too big".to_owned()));
+        }
+        if self.connect < 2 || self.connect > self.width.max(self.height) {
+            return Err(GameError::InvalidConfig(
+                "the winning line doesn't fit on the board".to_owned(),
+            ));
+        }
+        // A line that goes all the way around would need the same cell twice
+        if self.rules == Rules::Cylinder && self.connect > self.width {
+            return Err(GameError::InvalidConfig(
+                "the winning line is longer than the way around the cylinder".to_owned(),
+            ));
+        }
+        // Filling the board a row at a time needs the gaps in view
+        if self.rules == Rules::Pop10 && self.fog_of_war {
+            return Err(GameError::InvalidConfig(
+                "Pop 10 can't be played in fog of war".to_owned(),
+            ));
+        }
+        // The player who makes a line loses, so there has to be exactly one other player to win
+        if self.misere && (self.players != 2 || self.rules == Rules::Pop10) {
+            return Err(GameError::InvalidConfig(
+                "misère is only played by two players, and not with Pop 10".to_owned(),
+            ));
+        }
+        let cells = self.width * self.height;
+        match self.blockers {
+            Blockers::None => {}
+            Blockers::Fixed(mask) if cells < MAX_CELL_BITS && mask >> cells != 0 => {
+                return Err(GameError::InvalidConfig(
+                    "a blocker is outside the board".to_owned(),
+                ));
+            }
+            // Pieces can only be dropped into columns whose top cell is empty
+            Blockers::Fixed(mask) if (0..self.width).all(|col| mask >> col & 1 != 0) => {
+                return Err(GameError::InvalidConfig(
+                    "the blockers leave no column to drop into".to_owned(),
+                ));
+            }
+            Blockers::Fixed(_) => {}
+            Blockers::Random { count, .. } if count >= cells => {
+                return Err(GameError::InvalidConfig(
+                    "there are too many blockers to leave room to play".to_owned(),
+                ));
+            }
+            Blockers::Random { .. } => {}
+        }
+        // Cells under a floating blocker could never be filled
+        if self.blockers != Blockers::None && self.rules == Rules::Pop10 {
+            return Err(GameError::InvalidConfig(
+                "Pop 10 can't be played with blockers".to_owned(),
+            ));
+        }
+        if self.players < 2 || self.players > MAX_PLAYERS {
+            return Err(GameError::InvalidConfig(format!(
+                "a game needs between 2 and {MAX_PLAYERS} players"
+            )));
+        }
+        Ok(())
+    }
+
+    // Whether this is the classic 7x6 connect four game
+    pub fn is_standard(&self) -> bool {
+        *self == GameConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_standard() {
+        let config = GameConfig::default();
+        assert!(config.is_standard());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_accepts_variant_sizes() {
+        for (width, height, connect) in [(8, 7, 4), (9, 7, 4), (5, 4, 3), (9, 7, 6), (15, 7, 5)] {
+            let config = GameConfig::new(width, height, connect).unwrap();
+            assert!(!config.is_standard());
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_sizes() {
+        assert!(GameConfig::new(0, 6, 4).is_err());
+        assert!(GameConfig::new(7, 0, 4).is_err());
+        assert!(GameConfig::new(17, 7, 4).is_err());
+        assert!(GameConfig::new(7, 6, 8).is_err());
+        assert!(GameConfig::new(7, 6, 1).is_err());
+    }
+
+    #[test]
+    fn test_rules_default_when_missing() {
+        let config: GameConfig =
+            serde_json::from_str(r#"{"width":7,"height":6,"connect":4}"#).unwrap();
+        assert!(config.is_standard());
+        assert!(!config.with_rules(Rules::PopOut).is_standard());
+    }
+
+    #[test]
+    fn test_cylinder_line_fits_around() {
+        let config = GameConfig::new(4, 7, 5).unwrap();
+        assert!(config.with_rules(Rules::Cylinder).validate().is_err());
+        let config = GameConfig::new(5, 7, 5).unwrap();
+        assert!(config.with_rules(Rules::Cylinder).validate().is_ok());
+    }
+
+    #[test]
+    fn test_fog_of_war() {
+        let config = GameConfig::default().with_fog_of_war(true);
+        assert!(!config.is_standard());
+        assert!(config.validate().is_ok());
+        assert!(config.with_rules(Rules::Pop10).validate().is_err());
+    }
+
+    #[test]
+    fn test_misere() {
+        let config = GameConfig::default().with_misere(true);
+        assert!(config.validate().is_ok());
+        assert!(config.with_players(3).is_err());
+        assert!(config.with_rules(Rules::Pop10).validate().is_err());
+    }
+
+    #[test]
+    fn test_players() {
+        assert_eq!(GameConfig::default().players, 2);
+        let config = GameConfig::for_players(4).unwrap();
+        assert_eq!((config.width, config.height, config.players), (10, 8, 4));
+        assert!(GameConfig::default().with_players(1).is_err());
+        assert!(GameConfig::default().with_players(5).is_err());
+    }
+
+    #[test]
+    fn test_blockers() {
+        let config =
+            GameConfig::default().with_blockers(Blockers::fixed(7, [(5, 3), (2, 0)]).unwrap());
+        assert_eq!(config.blockers, Blockers::Fixed(1 << 38 | 1 << 14));
+        assert!(!config.is_standard());
+        assert!(config.validate().is_ok());
+        assert!(config.with_rules(Rules::Pop10).validate().is_err());
+        let outside = GameConfig::default().with_blockers(Blockers::Fixed(1 << 42));
+        assert!(outside.validate().is_err());
+        assert!(Blockers::fixed(7, [(18, 2)]).is_err());
+        let top_row = Blockers::fixed(7, (0..7).map(|col| (0, col))).unwrap();
+        assert!(
+            GameConfig::default()
+                .with_blockers(top_row)
+                .validate()
+                .is_err()
+        );
+        let all_but_one = Blockers::fixed(7, (1..7).map(|col| (0, col))).unwrap();
+        assert!(
+            GameConfig::default()
+                .with_blockers(all_but_one)
+                .validate()
+                .is_ok()
+        );
+        assert!(Blockers::fixed(7, [(0, 7)]).is_err());
+        assert!(Blockers::fixed(usize::MAX, [(2, 0)]).is_err());
+        let random = Blockers::Random { count: 42, seed: 7 };
+        assert!(
+            GameConfig::default()
+                .with_blockers(random)
+                .validate()
+                .is_err()
+        );
+        // Older clients leave blockers out
+        let config: GameConfig =
+            serde_json::from_str(r#"{"width":7,"height":6,"connect":4}"#).unwrap();
+        assert_eq!(config.blockers, Blockers::None);
+        let json = serde_json::to_string(&GameConfig::default().with_blockers(random)).unwrap();
+        let config: GameConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.blockers, random);
+    }
+}
@@ -1,10 +1,36 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Error, Serialize, Deserialize)]
+use crate::player::Player;
+
+#[derive(Debug, Clone, Error, Serialize, Deserialize)]
 pub enum GameError {
     #[error("column is full")]
     ColumnIsFull,
     #[error("usize {0} is out of bounds")]
     OutOfBounds(usize),
+    #[error("'{0}' is not a column in move notation")]
+    InvalidNotation(char),
+    #[error("move {ply} in column {col} is illegal")]
+    IllegalMove { ply: usize, col: usize },
+    #[error("invalid game config: {0}")]
+    InvalidConfig(String),
+    #[error("pieces can't be popped out right now")]
+    PopNotAllowed,
+    #[error("only your own pieces can be popped out of the bottom row")]
+    NotYourPiece,
+    #[error("the lowest row has to be filled first")]
+    RowNotFilled,
+    #[error("pop one of your pieces out of the bottom row")]
+    MustPop,
+    #[error("drop the popped piece back into the board")]
+    MustReturnPiece,
+    #[error("it is {0}'s turn")]
+    NotYourTurn(Player),
+    #[error("the game is already over")]
+    GameAlreadyOver,
+    #[error("spectators can't make moves")]
+    SpectatorCannotMove,
+    #[error("computer opponents can't play {0}")]
+    UnsupportedByBot(String),
 }
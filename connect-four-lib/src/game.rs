@@ -1,32 +1,133 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
 use crate::{
     board::{Board, Column, Row},
+    config::{Blockers, GameConfig, Rules},
     errors::GameError,
-    player::Player,
+    player::{MAX_PLAYERS, Player},
 };
 
-use strum::IntoEnumIterator;
-
+#[derive(Clone)]
 pub struct Game {
+    config: GameConfig,
     board: Board,
     status: GameStatus,
     current_player: Player,
+    // Every move played so far, oldest first
+    history: Vec<Move>,
+    // Moves taken back with `undo`, most recent last, cleared by any new move
+    undone: Vec<Move>,
+    // Pop 10 only: what the current player has to do next and how many pieces each player
+    // has collected
+    phase: Phase,
+    captured: [u32; MAX_PLAYERS],
+    // Players that surrendered, indexed by `Player::index`. Their turns are skipped.
+    eliminated: [bool; MAX_PLAYERS],
+    // Every surrender with how many moves had been played when it happened. Moves from
+    // before a surrender can't be undone.
+    surrenders: Vec<(usize, Player)>,
+}
+
+// Pieces a player has to collect to win Pop 10
+pub const POP_10_TARGET: u32 = 10;
+
+// Stages of a Pop 10 game. Other rules stay in `Phase::Filling` the whole game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    // Players take turns filling the board from the bottom row up, lines don't count
+    Filling,
+    // The player pops one of their pieces out of the bottom row
+    Popping,
+    // The popped piece wasn't part of a line and has to be dropped back in
+    Returning,
+}
+
+// A single piece dropped into or popped out of the board. `row` is where the piece landed,
+// or the bottom row for a pop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub col: Column,
+    pub row: Row,
+    pub player: Player,
+    pub kind: MoveKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MoveKind {
+    #[default]
+    Drop,
+    // Only allowed with `Rules::PopOut` and `Rules::Pop10`
+    Pop,
 }
 
 impl Game {
-    pub fn new() -> Self {
+    // Expects a config that passed `GameConfig::validate`
+    pub fn new(config: GameConfig) -> Self {
         Game {
-            board: Board::default(),
+            config,
+            board: starting_board(config),
             status: GameStatus::Playing,
             current_player: Player::One,
+            history: Vec::new(),
+            undone: Vec::new(),
+            phase: Phase::Filling,
+            captured: [0; MAX_PLAYERS],
+            eliminated: [false; MAX_PLAYERS],
+            surrenders: Vec::new(),
         }
     }
 
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    // Pieces `player` has collected in Pop 10
+    pub fn captured(&self, player: Player) -> u32 {
+        player.index().map_or(0, |index| self.captured[index])
+    }
+
+    // Seated players, in turn order
+    pub fn players(&self) -> impl Iterator<Item = Player> + use<> {
+        Player::SEATS.into_iter().take(self.config.players)
+    }
+
+    pub fn is_eliminated(&self, player: Player) -> bool {
+        player.index().is_some_and(|index| self.eliminated[index])
+    }
+
+    // Players still taking turns
+    pub fn remaining_players(&self) -> impl Iterator<Item = Player> + '_ {
+        self.players().filter(|player| !self.is_eliminated(*player))
+    }
+
+    pub fn config(&self) -> GameConfig {
+        self.config
+    }
+
     pub fn get_board(&self) -> Board {
         self.board
     }
 
+    // The board as `player` is allowed to see it. In fog of war that is their own pieces and
+    // the ones touching them, and everything once the game is over.
+    pub fn view_for(&self, player: Player) -> Board {
+        if self.config.fog_of_war && !self.is_over() {
+            self.board.visible_to(player)
+        } else {
+            self.board
+        }
+    }
+
     pub fn is_over(&self) -> bool {
-        self.status == GameStatus::Won(Player::One) || self.status == GameStatus::Won(Player::Two)
+        self.status != GameStatus::Playing
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.status == GameStatus::Draw
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.status
     }
 
     pub fn end_game(&mut self) {
@@ -37,32 +138,334 @@ impl Game {
         self.current_player
     }
 
+    // Hands the turn to the next player in turn order that hasn't been eliminated
     pub fn swap_players(&mut self) {
-        match self.current_player() {
-            Player::One => self.current_player = Player::Two,
-            Player::Two => self.current_player = Player::One,
-            _ => {}
+        let Some(current) = self.current_player().index() else {
+            return;
+        };
+        let players = self.config.players;
+        if let Some(next) = (1..=players)
+            .filter_map(|offset| Player::from_index((current + offset) % players))
+            .find(|player| !self.is_eliminated(*player))
+        {
+            self.current_player = next;
+        }
+    }
+
+    // Takes `player` out of the game, ending it if only one player is left
+    fn eliminate(&mut self, player: Player) {
+        let Some(index) = player.index() else {
+            return;
+        };
+        if self.is_over() || self.eliminated[index] {
+            return;
+        }
+        self.eliminated[index] = true;
+        self.surrenders.push((self.history.len(), player));
+        // Undone moves may belong to the player that just left
+        self.undone.clear();
+        let remaining: Vec<Player> = self.remaining_players().collect();
+        if let [last] = remaining[..] {
+            self.status = GameStatus::Won(last);
+        } else if self.current_player() == player {
+            self.swap_players();
         }
     }
 
-    pub fn make_move(&mut self, col: &Column) -> Result<(Column, Row), GameError> {
-        if self.board.is_slot_full(col) {
+    // Whether `player` is allowed to make a move right now
+    fn check_turn(&self, player: Player) -> Result<(), GameError> {
+        if player == Player::Spectator {
+            return Err(GameError::SpectatorCannotMove);
+        }
+        if self.is_over() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if player != self.current_player() {
+            return Err(GameError::NotYourTurn(self.current_player()));
+        }
+        Ok(())
+    }
+
+    // Drops `player`'s piece into `col`, which has to be their turn
+    pub fn make_move(&mut self, player: Player, col: &Column) -> Result<(Column, Row), GameError> {
+        self.check_turn(player)?;
+        let placed = self.drop_piece(col)?;
+        self.undone.clear();
+        Ok(placed)
+    }
+
+    fn drop_piece(&mut self, col: &Column) -> Result<(Column, Row), GameError> {
+        if usize::from(col) >= self.config.width {
+            return Err(GameError::OutOfBounds(col.into()));
+        }
+        if self.config.rules == Rules::Pop10 {
+            return self.drop_pop_10(*col);
+        }
+        let Some(row) = self.board.drop_row(*col) else {
             return Err(GameError::ColumnIsFull);
+        };
+        self.board.insert_piece(row, *col, self.current_player());
+        self.history.push(Move {
+            col: *col,
+            row,
+            player: self.current_player(),
+            kind: MoveKind::Drop,
+        });
+        if let Some(winner) = self.check_for_winner() {
+            self.status = GameStatus::Won(winner);
         }
-        for row in Row::iter().rev() {
-            if self.board.get(row, *col).is_none() {
-                self.board.insert_piece(row, *col, self.current_player());
-                if self.check_for_winner().is_some() {
-                    self.end_game();
-                }
-                self.swap_players();
+        self.swap_players();
+        // Under PopOut a full board only ends the game if the next player can't pop
+        if !self.is_over() && self.board.is_full() && !self.can_pop_out(self.current_player()) {
+            self.status = GameStatus::Draw;
+        }
+
+        Ok((*col, row))
+    }
+
+    // Whether `player` has a piece in the bottom row to pop out under PopOut
+    fn can_pop_out(&self, player: Player) -> bool {
+        let bottom = Row::new(self.config.height - 1);
+        self.config.rules == Rules::PopOut
+            && self
+                .board
+                .columns()
+                .any(|col| self.board.get(bottom, col) == Some(player))
+    }
+
+    // PopOut: removes one of the current player's pieces from the bottom of `col` instead
+    // of dropping one. Returns the row the piece was popped from.
+    pub fn pop_out(&mut self, player: Player, col: &Column) -> Result<(Column, Row), GameError> {
+        self.check_turn(player)?;
+        let popped = self.pop_piece(col)?;
+        self.undone.clear();
+        Ok(popped)
+    }
+
+    fn pop_piece(&mut self, col: &Column) -> Result<(Column, Row), GameError> {
+        let pop_10 = match self.config.rules {
+            Rules::Standard | Rules::Cylinder => return Err(GameError::PopNotAllowed),
+            Rules::PopOut => false,
+            Rules::Pop10 => true,
+        };
+        if usize::from(col) >= self.config.width {
+            return Err(GameError::OutOfBounds(col.into()));
+        }
+        let row = Row::new(self.config.height - 1);
+        if pop_10 && self.phase != Phase::Popping {
+            return Err(match self.phase {
+                Phase::Returning => GameError::MustReturnPiece,
+                _ => GameError::PopNotAllowed,
+            });
+        }
+        if self.board.get(row, *col) != Some(self.current_player()) {
+            return Err(GameError::NotYourPiece);
+        }
+        if pop_10 {
+            return Ok(self.pop_pop_10(*col, row));
+        }
+        self.board.pop_piece(*col);
+        self.history.push(Move {
+            col: *col,
+            row,
+            player: self.current_player(),
+            kind: MoveKind::Pop,
+        });
+        // Everything above the popped piece falls, which can finish lines for several players
+        // at once. The popper's line counts if they have one, otherwise the first line in turn
+        // order after the popper decides the game.
+        let connect = self.config.connect;
+        let popper = self.current_player().index().unwrap_or(0);
+        let players = self.config.players;
+        let line = (0..players)
+            .filter_map(|offset| Player::from_index((popper + offset) % players))
+            .filter(|player| !self.is_eliminated(*player))
+            .find(|player| self.board.has_connected(*player, connect));
+        if let Some(line) = line {
+            self.status = GameStatus::Won(self.winner_for_line(line));
+        }
+        self.swap_players();
+
+        Ok((*col, row))
+    }
+
+    // Pop 10: the board is filled a row at a time from the bottom, then pieces are dropped
+    // back in after a pop that didn't collect anything
+    fn drop_pop_10(&mut self, col: Column) -> Result<(Column, Row), GameError> {
+        if self.phase == Phase::Popping {
+            return Err(GameError::MustPop);
+        }
+        let Some(row) = self.board.drop_row(col) else {
+            return Err(GameError::ColumnIsFull);
+        };
+        if self.phase == Phase::Filling {
+            // The lowest row with a gap in it has to be filled first
+            let lowest_open = self.board.rows().rev().find(|row| {
+                self.board
+                    .columns()
+                    .any(|col| self.board.get(*row, col).is_none())
+            });
+            if lowest_open != Some(row) {
+                return Err(GameError::RowNotFilled);
+            }
+        }
+        self.board.insert_piece(row, col, self.current_player());
+        self.history.push(Move {
+            col,
+            row,
+            player: self.current_player(),
+            kind: MoveKind::Drop,
+        });
+        if self.phase == Phase::Returning || self.board.is_full() {
+            self.phase = Phase::Popping;
+        }
+        self.swap_players();
+        self.skip_players_without_pops();
+        Ok((col, row))
+    }
 
-                return Ok((*col, row));
+    // Pop 10: a piece that was part of a line is collected and the player goes again,
+    // anything else has to be dropped back into the board
+    fn pop_pop_10(&mut self, col: Column, row: Row) -> (Column, Row) {
+        let player = self.current_player();
+        let collected = self.board.in_line(row, col, self.config.connect);
+        self.board.pop_piece(col);
+        self.history.push(Move {
+            col,
+            row,
+            player,
+            kind: MoveKind::Pop,
+        });
+        if !collected {
+            self.phase = Phase::Returning;
+            return (col, row);
+        }
+        if let Some(index) = player.index() {
+            self.captured[index] += 1;
+            if self.captured[index] >= POP_10_TARGET {
+                self.end_game();
+            }
+        }
+        self.skip_players_without_pops();
+        (col, row)
+    }
+
+    // Pop 10: a player without a piece in the bottom row loses their turn, and the game is
+    // drawn if nobody can pop
+    fn skip_players_without_pops(&mut self) {
+        if self.phase != Phase::Popping || self.is_over() {
+            return;
+        }
+        let bottom = Row::new(self.config.height - 1);
+        let can_pop = |game: &Game| {
+            game.board
+                .columns()
+                .any(|col| game.board.get(bottom, col) == Some(game.current_player()))
+        };
+        for _ in 0..self.config.players {
+            if can_pop(self) {
+                return;
+            }
+            self.swap_players();
+        }
+        self.status = GameStatus::Draw;
+    }
+
+    // Rebuilds the game from its history, for rules where moves can't simply be taken back
+    fn replay(&mut self) {
+        let history = std::mem::take(&mut self.history);
+        let undone = std::mem::take(&mut self.undone);
+        let surrenders = std::mem::take(&mut self.surrenders);
+        *self = Game::new(self.config);
+        let mut surrenders = surrenders.into_iter().peekable();
+        for (played, m) in history.into_iter().enumerate() {
+            while let Some((_, player)) = surrenders.next_if(|(at, _)| *at == played) {
+                self.eliminate(player);
+            }
+            let replayed = match m.kind {
+                MoveKind::Drop => self.drop_piece(&m.col),
+                MoveKind::Pop => self.pop_piece(&m.col),
+            };
+            replayed.expect("moves in the history were legal when they were played");
+        }
+        for (_, player) in surrenders {
+            self.eliminate(player);
+        }
+        self.undone = undone;
+    }
+
+    // Takes back the last move, handing the turn back to whoever made it. Stops at the last
+    // surrender, so a game that was given up stays over.
+    pub fn undo(&mut self) -> Option<Move> {
+        if self
+            .surrenders
+            .last()
+            .is_some_and(|(at, _)| *at >= self.history.len())
+        {
+            return None;
+        }
+        if self.config.rules == Rules::Pop10 {
+            // Collected pieces and skipped turns depend on everything before the move
+            let last = self.history.pop()?;
+            self.replay();
+            self.undone.push(last);
+            return Some(last);
+        }
+        let last = self.history.pop()?;
+        match last.kind {
+            MoveKind::Drop => self.board.remove_piece(last.row, last.col),
+            MoveKind::Pop => self.board.push_piece(last.col, last.player),
+        }
+        self.current_player = last.player;
+        // Moves are only made while the game is being played, and the game can't have been
+        // ended by a surrender since
+        self.status = GameStatus::Playing;
+        self.undone.push(last);
+        Some(last)
+    }
+
+    // Plays the most recently undone move again
+    pub fn redo(&mut self) -> Option<Move> {
+        let next = self.undone.pop()?;
+        match next.kind {
+            MoveKind::Drop => self.drop_piece(&next.col).ok()?,
+            MoveKind::Pop => self.pop_piece(&next.col).ok()?,
+        };
+        Some(next)
+    }
+
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    // Builds a game from the column-sequence notation used by solver test sets, where each
+    // character is the 1-based column of the next move (e.g. "4453261"). Positions are always
+    // on the standard board
+    pub fn from_notation(moves: &str) -> Result<Self, GameError> {
+        let mut game = Game::default();
+        for (i, c) in moves.chars().enumerate() {
+            let col = match c.to_digit(10) {
+                Some(digit @ 1..=7) => digit as usize,
+                _ => return Err(GameError::InvalidNotation(c)),
+            };
+            let illegal = GameError::IllegalMove { ply: i + 1, col };
+            if game.is_over() {
+                return Err(illegal);
             }
+            game.make_move(game.current_player(), &Column::new(col - 1))
+                .map_err(|_| illegal)?;
         }
+        Ok(game)
+    }
 
-        // Should never reach this
-        Ok((Column::One, Row::One))
+    // Inverse of `Game::from_notation`. The notation has no way to write down a pop, so
+    // games that popped a piece don't round trip. Columns past the ninth have no digit, so
+    // games on wider boards that used them give `None`.
+    pub fn to_notation(&self) -> Option<String> {
+        self.history
+            .iter()
+            .map(|m| char::from_digit(u32::try_from(usize::from(m.col)).ok()? + 1, 10))
+            .collect()
     }
 
     pub fn get_winner(&self) -> Option<Player> {
@@ -73,83 +476,92 @@ impl Game {
         }
     }
 
+    // Who wins from the lines on the board: whoever has one, or in misère their opponent
     pub fn check_for_winner(&self) -> Option<Player> {
-        use crate::board::{Column, Row};
-        use strum::IntoEnumIterator;
-
-        let row_idx = |row: Row| usize::from(row);
-        let col_idx = |col: Column| usize::from(col);
-
-        for row in Row::iter() {
-            for col in Column::iter() {
-                let slot = self.board.get(row, col);
-                if let Some(player) = slot {
-                    // Horizontal
-                    if col_idx(col) <= 3
-                        && (0..4).all(|i| {
-                            self.board
-                                .get(row, Column::iter().nth(col_idx(col) + i).unwrap())
-                                == Some(player)
-                        })
-                    {
-                        return Some(player);
-                    }
-                    // Vertical
-                    if row_idx(row) <= 2
-                        && (0..4).all(|i| {
-                            self.board
-                                .get(Row::iter().nth(row_idx(row) + i).unwrap(), col)
-                                == Some(player)
-                        })
-                    {
-                        return Some(player);
-                    }
-                    // Diagonal down-right
-                    if col_idx(col) <= 3
-                        && row_idx(row) <= 2
-                        && (0..4).all(|i| {
-                            self.board.get(
-                                Row::iter().nth(row_idx(row) + i).unwrap(),
-                                Column::iter().nth(col_idx(col) + i).unwrap(),
-                            ) == Some(player)
-                        })
-                    {
-                        return Some(player);
-                    }
-                    // Diagonal down-left
-                    if col_idx(col) >= 3
-                        && row_idx(row) <= 2
-                        && (0..4).all(|i| {
-                            self.board.get(
-                                Row::iter().nth(row_idx(row) + i).unwrap(),
-                                Column::iter().nth(col_idx(col) - i).unwrap(),
-                            ) == Some(player)
-                        })
-                    {
-                        return Some(player);
-                    }
-                }
-            }
+        let line = self.board.winner(self.config.connect)?;
+        Some(self.winner_for_line(line))
+    }
+
+    fn winner_for_line(&self, player: Player) -> Player {
+        if !self.config.misere {
+            return player;
         }
-        None
+        // Misère is only played by two players, see `GameConfig::validate`
+        player
+            .index()
+            .and_then(|index| Player::from_index((index + 1) % self.config.players))
+            .unwrap_or(player)
     }
 
-    pub fn surrender(&mut self, player_surrendering: Player) {
-        if player_surrendering == Player::One {
-            self.status = GameStatus::Won(Player::Two)
-        } else if player_surrendering == Player::Two {
-            self.status = GameStatus::Won(Player::One)
+    // Position of the winner in turn order, 0 for player one
+    pub fn winner_index(&self) -> Option<usize> {
+        self.get_winner()?.index()
+    }
+
+    // Cells of the lines that ended the game: the winner's, or in misère the loser's. Empty
+    // when nobody won by making a line, as after a surrender or in Pop 10.
+    pub fn winning_lines(&self) -> Vec<Vec<(Row, Column)>> {
+        let Some(winner) = self.get_winner() else {
+            return Vec::new();
+        };
+        if self.config.rules == Rules::Pop10 {
+            return Vec::new();
         }
+        self.players()
+            .filter(|player| (*player == winner) != self.config.misere)
+            .flat_map(|player| self.board.lines(player, self.config.connect))
+            .collect()
+    }
+
+    // The surrendering player is eliminated, so with two players the other one wins
+    pub fn surrender(&mut self, player_surrendering: Player) {
+        self.eliminate(player_surrendering);
     }
 }
 
 impl Default for Game {
     fn default() -> Self {
-        Self::new()
+        Self::new(GameConfig::default())
+    }
+}
+
+// Empty board for `config`, apart from its blockers
+fn starting_board(config: GameConfig) -> Board {
+    let mut board =
+        Board::new(config.width, config.height).with_wrap_around(config.rules == Rules::Cylinder);
+    match config.blockers {
+        Blockers::None => {}
+        Blockers::Fixed(mask) => {
+            for row in board.rows() {
+                for col in board.columns() {
+                    if mask >> (usize::from(row) * config.width + usize::from(col)) & 1 != 0 {
+                        board.place_blocker(row, col);
+                    }
+                }
+            }
+        }
+        // Random blockers are dropped in like pieces, so none of them float
+        Blockers::Random { count, seed } => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for _ in 0..count {
+                let open: Vec<Column> = board
+                    .columns()
+                    .filter(|col| !board.is_slot_full(col))
+                    .collect();
+                if open.is_empty() {
+                    break;
+                }
+                let col = open[rng.gen_range(0..open.len())];
+                if let Some(row) = board.drop_row(col) {
+                    board.place_blocker(row, col);
+                }
+            }
+        }
     }
+    board
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameStatus {
     Playing,
     Won(Player),
@@ -163,12 +575,18 @@ mod tests {
     #[test]
     fn test_horizontal_win() {
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::One,
+            ..Game::default()
         };
-        let row = Row::Six;
-        for col in [Column::One, Column::Two, Column::Three, Column::Four] {
+        let row = Row::new(5);
+        for col in [
+            Column::new(0),
+            Column::new(1),
+            Column::new(2),
+            Column::new(3),
+        ] {
             game.board.insert_piece(row, col, Player::One);
         }
         assert_eq!(game.check_for_winner(), Some(Player::One));
@@ -177,12 +595,13 @@ mod tests {
     #[test]
     fn test_vertical_win() {
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::Two,
+            ..Game::default()
         };
-        let col = Column::Four;
-        for row in [Row::Six, Row::Five, Row::Four, Row::Three] {
+        let col = Column::new(3);
+        for row in [Row::new(5), Row::new(4), Row::new(3), Row::new(2)] {
             game.board.insert_piece(row, col, Player::Two);
         }
         assert_eq!(game.check_for_winner(), Some(Player::Two));
@@ -191,47 +610,57 @@ mod tests {
     #[test]
     fn test_diagonal_down_right_win() {
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::One,
+            ..Game::default()
         };
-        game.board.insert_piece(Row::Six, Column::One, Player::One);
-        game.board.insert_piece(Row::Five, Column::Two, Player::One);
         game.board
-            .insert_piece(Row::Four, Column::Three, Player::One);
+            .insert_piece(Row::new(5), Column::new(0), Player::One);
+        game.board
+            .insert_piece(Row::new(4), Column::new(1), Player::One);
         game.board
-            .insert_piece(Row::Three, Column::Four, Player::One);
+            .insert_piece(Row::new(3), Column::new(2), Player::One);
+        game.board
+            .insert_piece(Row::new(2), Column::new(3), Player::One);
         assert_eq!(game.check_for_winner(), Some(Player::One));
     }
 
     #[test]
     fn test_diagonal_down_left_win() {
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::Two,
+            ..Game::default()
         };
         game.board
-            .insert_piece(Row::Three, Column::Four, Player::Two);
+            .insert_piece(Row::new(2), Column::new(3), Player::Two);
+        game.board
+            .insert_piece(Row::new(3), Column::new(2), Player::Two);
+        game.board
+            .insert_piece(Row::new(4), Column::new(1), Player::Two);
         game.board
-            .insert_piece(Row::Four, Column::Three, Player::Two);
-        game.board.insert_piece(Row::Five, Column::Two, Player::Two);
-        game.board.insert_piece(Row::Six, Column::One, Player::Two);
+            .insert_piece(Row::new(5), Column::new(0), Player::Two);
         assert_eq!(game.check_for_winner(), Some(Player::Two));
     }
 
     #[test]
     fn test_no_winner() {
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::One,
+            ..Game::default()
         };
-        game.board.insert_piece(Row::Six, Column::One, Player::One);
-        game.board.insert_piece(Row::Six, Column::Two, Player::Two);
         game.board
-            .insert_piece(Row::Six, Column::Three, Player::One);
-        game.board.insert_piece(Row::Six, Column::Four, Player::Two);
+            .insert_piece(Row::new(5), Column::new(0), Player::One);
+        game.board
+            .insert_piece(Row::new(5), Column::new(1), Player::Two);
+        game.board
+            .insert_piece(Row::new(5), Column::new(2), Player::One);
+        game.board
+            .insert_piece(Row::new(5), Column::new(3), Player::Two);
         assert_eq!(game.check_for_winner(), None);
     }
 
@@ -239,9 +668,10 @@ mod tests {
     fn test_draw_game() {
         use crate::board::{Column, Row};
         let mut game = Game {
-            board: Board::new(),
+            board: Board::default(),
             status: GameStatus::Playing,
             current_player: Player::One,
+            ..Game::default()
         };
         // Pattern that fills the board avoiding any 4-in-a-row for both players
         // Board fill absolutely guaranteed to avoid any connect four:
@@ -254,7 +684,7 @@ mod tests {
                 Player::One,
                 Player::Two,
                 Player::One,
-            ], // Row::Six
+            ], // Row 5
             [
                 Player::Two,
                 Player::One,
@@ -263,7 +693,7 @@ mod tests {
                 Player::Two,
                 Player::One,
                 Player::Two,
-            ], // Row::Five
+            ], // Row 4
             [
                 Player::Two,
                 Player::One,
@@ -272,7 +702,7 @@ mod tests {
                 Player::Two,
                 Player::One,
                 Player::Two,
-            ], // Row::Four (repeat previous for safety)
+            ], // Row 3 (repeat previous for safety)
             [
                 Player::One,
                 Player::Two,
@@ -281,7 +711,7 @@ mod tests {
                 Player::One,
                 Player::Two,
                 Player::One,
-            ], // Row::Three
+            ], // Row 2
             [
                 Player::One,
                 Player::Two,
@@ -290,7 +720,7 @@ mod tests {
                 Player::One,
                 Player::Two,
                 Player::One,
-            ], // Row::Two (repeat previous for safety)
+            ], // Row 1 (repeat previous for safety)
             [
                 Player::Two,
                 Player::One,
@@ -299,24 +729,24 @@ mod tests {
                 Player::Two,
                 Player::One,
                 Player::Two,
-            ], // Row::One
+            ], // Row 0
         ];
         let rows = [
-            Row::Six,
-            Row::Five,
-            Row::Four,
-            Row::Three,
-            Row::Two,
-            Row::One,
+            Row::new(5),
+            Row::new(4),
+            Row::new(3),
+            Row::new(2),
+            Row::new(1),
+            Row::new(0),
         ];
         let cols = [
-            Column::One,
-            Column::Two,
-            Column::Three,
-            Column::Four,
-            Column::Five,
-            Column::Six,
-            Column::Seven,
+            Column::new(0),
+            Column::new(1),
+            Column::new(2),
+            Column::new(3),
+            Column::new(4),
+            Column::new(5),
+            Column::new(6),
         ];
         for (i, row) in rows.iter().enumerate() {
             for (j, col) in cols.iter().enumerate() {
@@ -326,20 +756,43 @@ mod tests {
         assert_eq!(game.check_for_winner(), None);
         game.status = GameStatus::Draw;
         assert_eq!(game.status, GameStatus::Draw);
+        assert!(game.is_over());
+        assert_eq!(game.get_winner(), None);
+    }
+
+    // Fills the board with the same pattern as `test_draw_game`
+    const FULL_BOARD: [usize; 42] = [
+        0, 0, 2, 0, 0, 1, 0, 0, 1, 2, 1, 1, 4, 1, 1, 2, 2, 3, 2, 2, 3, 4, 3, 3, 6, 3, 3, 4, 4, 5,
+        4, 4, 5, 6, 5, 6, 6, 5, 6, 5, 5, 6,
+    ];
+
+    #[test]
+    fn test_full_board_ends_in_draw() {
+        let mut game = Game::default();
+        for col in FULL_BOARD {
+            assert!(!game.is_over());
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert_eq!(game.check_for_winner(), None);
+        assert!(game.is_over());
+        assert!(game.is_draw());
+        assert_eq!(game.status(), GameStatus::Draw);
+        assert_eq!(game.get_winner(), None);
     }
 
     #[test]
     fn test_full_column_detection() {
         use crate::board::{Column, Row};
-        let mut board = Board::new();
-        let col = Column::One;
+        let mut board = Board::default();
+        let col = Column::new(0);
         for row in [
-            Row::Six,
-            Row::Five,
-            Row::Four,
-            Row::Three,
-            Row::Two,
-            Row::One,
+            Row::new(5),
+            Row::new(4),
+            Row::new(3),
+            Row::new(2),
+            Row::new(1),
+            Row::new(0),
         ] {
             board.insert_piece(row, col, Player::One);
         }
@@ -349,21 +802,677 @@ mod tests {
     #[test]
     fn test_swap_player() {
         use crate::board::Column;
-        let mut game = Game::new();
+        let mut game = Game::default();
         assert!(game.current_player == Player::One);
-        let _ = game.make_move(&Column::One);
+        let _ = game.make_move(game.current_player(), &Column::new(0));
         assert!(game.current_player == Player::Two);
-        let _ = game.make_move(&Column::One);
+        let _ = game.make_move(game.current_player(), &Column::new(0));
         assert!(game.current_player == Player::One);
     }
 
     #[test]
     fn test_make_move() {
         use crate::board::{Column, Row};
-        let mut game = Game::new();
-        let (_, row) = game.make_move(&Column::One).unwrap();
-        assert!(row == Row::Six);
-        let (_, row) = game.make_move(&Column::One).unwrap();
-        assert!(row == Row::Five);
+        let mut game = Game::default();
+        let (_, row) = game
+            .make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert!(row == Row::new(5));
+        let (_, row) = game
+            .make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert!(row == Row::new(4));
+    }
+
+    #[test]
+    fn test_history_records_moves() {
+        let mut game = Game::default();
+        game.make_move(game.current_player(), &Column::new(3))
+            .unwrap();
+        game.make_move(game.current_player(), &Column::new(3))
+            .unwrap();
+        assert_eq!(
+            game.history(),
+            &[
+                Move {
+                    col: Column::new(3),
+                    row: Row::new(5),
+                    player: Player::One,
+                    kind: MoveKind::Drop,
+                },
+                Move {
+                    col: Column::new(3),
+                    row: Row::new(4),
+                    player: Player::Two,
+                    kind: MoveKind::Drop,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut game = Game::default();
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        game.make_move(game.current_player(), &Column::new(2))
+            .unwrap();
+
+        let undone = game.undo().unwrap();
+        assert_eq!(undone.col, Column::new(2));
+        assert_eq!(game.current_player(), Player::Two);
+        assert_eq!(game.get_board().get(Row::new(5), Column::new(2)), None);
+        assert_eq!(game.history().len(), 1);
+
+        let redone = game.redo().unwrap();
+        assert_eq!(redone, undone);
+        assert_eq!(game.current_player(), Player::One);
+        assert_eq!(
+            game.get_board().get(Row::new(5), Column::new(2)),
+            Some(Player::Two)
+        );
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn test_undo_restores_status() {
+        let mut game = Game::default();
+        for col in [
+            Column::new(0),
+            Column::new(1),
+            Column::new(0),
+            Column::new(1),
+            Column::new(0),
+        ] {
+            game.make_move(game.current_player(), &col).unwrap();
+        }
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!(game.get_winner(), Some(Player::One));
+
+        game.undo().unwrap();
+        assert_eq!(game.status(), GameStatus::Playing);
+        assert_eq!(game.current_player(), Player::One);
+        assert_eq!(game.check_for_winner(), None);
+
+        game.redo().unwrap();
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_new_move_clears_redo() {
+        let mut game = Game::default();
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.undo().unwrap();
+        game.make_move(game.current_player(), &Column::new(4))
+            .unwrap();
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.undo().map(|m| m.col), Some(Column::new(4)));
+        assert_eq!(game.undo(), None);
+    }
+
+    #[test]
+    fn test_notation_round_trip() {
+        let game = Game::from_notation("4453261").unwrap();
+        assert_eq!(game.history().len(), 7);
+        assert_eq!(
+            game.get_board().get(Row::new(5), Column::new(3)),
+            Some(Player::One)
+        );
+        assert_eq!(
+            game.get_board().get(Row::new(4), Column::new(3)),
+            Some(Player::Two)
+        );
+        assert_eq!(
+            game.get_board().get(Row::new(5), Column::new(0)),
+            Some(Player::One)
+        );
+        assert_eq!(game.current_player(), Player::Two);
+        assert_eq!(game.to_notation().as_deref(), Some("4453261"));
+        assert_eq!(
+            Game::from_notation("").unwrap().to_notation().as_deref(),
+            Some("")
+        );
+
+        let mut wide = Game::new(GameConfig::new(15, 7, 4).unwrap());
+        wide.make_move(Player::One, &Column::new(8)).unwrap();
+        assert_eq!(wide.to_notation().as_deref(), Some("9"));
+        wide.make_move(Player::Two, &Column::new(12)).unwrap();
+        assert_eq!(wide.to_notation(), None);
+    }
+
+    #[test]
+    fn test_notation_rejects_bad_characters() {
+        assert!(matches!(
+            Game::from_notation("448"),
+            Err(GameError::InvalidNotation('8'))
+        ));
+        assert!(matches!(
+            Game::from_notation("40"),
+            Err(GameError::InvalidNotation('0'))
+        ));
+        assert!(matches!(
+            Game::from_notation("4 4"),
+            Err(GameError::InvalidNotation(' '))
+        ));
+    }
+
+    #[test]
+    fn test_notation_rejects_illegal_moves() {
+        // Seventh piece in column one
+        assert!(matches!(
+            Game::from_notation("1111111"),
+            Err(GameError::IllegalMove { ply: 7, col: 1 })
+        ));
+        // Player one has already won with a vertical four
+        assert!(matches!(
+            Game::from_notation("12121213"),
+            Err(GameError::IllegalMove { ply: 8, col: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_custom_config() {
+        let mut game = Game::new(GameConfig::new(9, 7, 5).unwrap());
+        assert!(matches!(
+            game.make_move(game.current_player(), &Column::new(9)),
+            Err(GameError::OutOfBounds(9))
+        ));
+        let (_, row) = game
+            .make_move(game.current_player(), &Column::new(8))
+            .unwrap();
+        assert_eq!(row, Row::new(6));
+        // Player one plays along the bottom, player two stacks on top
+        for col in [8, 7, 7, 6, 6, 5] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        // Four in a row isn't enough
+        assert!(!game.is_over());
+        game.make_move(game.current_player(), &Column::new(5))
+            .unwrap();
+        game.make_move(game.current_player(), &Column::new(4))
+            .unwrap();
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_three_players_take_turns() {
+        let mut game = Game::new(GameConfig::for_players(3).unwrap());
+        for (col, player) in [(0, Player::One), (1, Player::Two), (2, Player::Three)] {
+            assert_eq!(game.current_player(), player);
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert_eq!(game.current_player(), Player::One);
+        game.undo();
+        assert_eq!(game.current_player(), Player::Three);
+    }
+
+    #[test]
+    fn test_three_player_win() {
+        let mut game = Game::new(GameConfig::for_players(3).unwrap());
+        // Player three builds along the bottom while the others stack in their columns
+        for col in [0, 1, 2, 0, 1, 3, 0, 1, 4, 7, 8, 5] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert_eq!(game.get_winner(), Some(Player::Three));
+        assert_eq!(game.winner_index(), Some(2));
+    }
+
+    #[test]
+    fn test_surrender_eliminates_player() {
+        let mut game = Game::new(GameConfig::for_players(3).unwrap());
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.surrender(Player::Two);
+        assert!(game.is_eliminated(Player::Two));
+        assert!(!game.is_over());
+        // Player two's turn is skipped from now on
+        assert_eq!(game.current_player(), Player::Three);
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!(game.current_player(), Player::One);
+        game.surrender(Player::One);
+        assert_eq!(game.get_winner(), Some(Player::Three));
+    }
+
+    #[test]
+    fn test_undo_stops_at_a_surrender() {
+        let mut game = Game::new(GameConfig::for_players(3).unwrap());
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.surrender(Player::Two);
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        assert_eq!(game.undo().map(|m| m.player), Some(Player::Three));
+        assert_eq!(game.current_player(), Player::Three);
+        assert!(game.is_eliminated(Player::Two));
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.history().len(), 1);
+
+        let mut game = Game::default();
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.surrender(Player::Two);
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_pop_10_undo_keeps_surrenders() {
+        let config = GameConfig::for_players(3).unwrap().with_rules(Rules::Pop10);
+        let mut game = Game::new(config);
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.surrender(Player::Two);
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        game.undo().unwrap();
+        assert!(game.is_eliminated(Player::Two));
+        assert_eq!(game.current_player(), Player::Three);
+        assert_eq!(game.undo(), None);
+    }
+
+    #[test]
+    fn test_two_player_surrender() {
+        let mut game = Game::default();
+        game.surrender(Player::One);
+        assert_eq!(game.get_winner(), Some(Player::Two));
+        assert_eq!(game.winner_index(), Some(1));
+    }
+
+    #[test]
+    fn test_narrow_cylinder_seam_wins() {
+        // Player one builds the bottom row across the seam while player two stacks elsewhere
+        for (width, connect, ones, twos) in [
+            (5, 4, [4, 0, 1, 2].as_slice(), [3, 3, 3].as_slice()),
+            (7, 5, &[6, 0, 1, 2, 3], &[5, 5, 5, 4]),
+        ] {
+            let config = GameConfig::new(width, 6, connect)
+                .unwrap()
+                .with_rules(Rules::Cylinder);
+            assert!(config.validate().is_ok());
+            let mut game = Game::new(config);
+            for (i, col) in ones.iter().enumerate() {
+                game.make_move(Player::One, &Column::new(*col)).unwrap();
+                if let Some(col) = twos.get(i) {
+                    game.make_move(Player::Two, &Column::new(*col)).unwrap();
+                }
+            }
+            assert_eq!(game.get_winner(), Some(Player::One), "{width} wide");
+        }
+    }
+
+    #[test]
+    fn test_misere_line_loses() {
+        let mut game = Game::new(GameConfig::default().with_misere(true));
+        for col in [0, 1, 0, 1, 0, 1, 0] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert_eq!(game.get_winner(), Some(Player::Two));
+        assert_eq!(game.check_for_winner(), Some(Player::Two));
+        game.undo();
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn test_misere_full_board_draws() {
+        let mut game = Game::new(GameConfig::new(3, 1, 3).unwrap().with_misere(true));
+        for col in [0, 1, 2] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert!(game.is_draw());
+    }
+
+    #[test]
+    fn test_misere_pop_out() {
+        let mut game = Game::new(
+            GameConfig::default()
+                .with_rules(Rules::PopOut)
+                .with_misere(true),
+        );
+        for col in [0, 1, 1, 2, 2, 3, 3, 0, 0, 6] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        // The pop gives both players a line, and the popper's line loses
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!(game.get_winner(), Some(Player::Two));
+    }
+
+    #[test]
+    fn test_fog_of_war_view() {
+        let mut game = Game::new(GameConfig::default().with_fog_of_war(true));
+        for col in [0, 6, 1] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        let bottom = Row::new(5);
+        let view = game.view_for(Player::Two);
+        assert_eq!(view.get(bottom, Column::new(6)), Some(Player::Two));
+        assert_eq!(view.get(bottom, Column::new(0)), None);
+        assert_eq!(game.view_for(Player::Spectator), game.get_board());
+        // Everything is revealed once the game is over
+        game.surrender(Player::Two);
+        assert_eq!(game.view_for(Player::Two), game.get_board());
+    }
+
+    fn pop_out_game(cols: &[usize]) -> Game {
+        let mut game = Game::new(GameConfig::default().with_rules(Rules::PopOut));
+        for col in cols {
+            game.make_move(game.current_player(), &Column::new(*col))
+                .unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn test_pop_out_goes_on_after_the_board_fills() {
+        let mut game = pop_out_game(&FULL_BOARD);
+        assert!(game.get_board().is_full());
+        assert!(!game.is_over());
+        assert!(matches!(
+            game.make_move(Player::One, &Column::new(0)),
+            Err(GameError::ColumnIsFull)
+        ));
+        game.pop_out(Player::One, &Column::new(0)).unwrap();
+        assert!(!game.get_board().is_full());
+        assert_eq!(game.current_player(), Player::Two);
+    }
+
+    #[test]
+    fn test_pop_needs_pop_out_rules() {
+        let mut game = Game::default();
+        game.make_move(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(0)),
+            Err(GameError::PopNotAllowed)
+        ));
+    }
+
+    #[test]
+    fn test_pop_only_own_pieces() {
+        let mut game = pop_out_game(&[0, 1]);
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(1)),
+            Err(GameError::NotYourPiece)
+        ));
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(2)),
+            Err(GameError::NotYourPiece)
+        ));
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(7)),
+            Err(GameError::OutOfBounds(7))
+        ));
+        let (col, row) = game
+            .pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!((col, row), (Column::new(0), Row::new(5)));
+        assert_eq!(game.get_board().get(Row::new(5), Column::new(0)), None);
+        assert_eq!(game.current_player(), Player::Two);
+        assert_eq!(game.history().last().unwrap().kind, MoveKind::Pop);
+    }
+
+    #[test]
+    fn test_pop_shifts_column_into_win() {
+        // Popping column 0 drops player one's piece next to their three in the second row
+        let mut game = pop_out_game(&[0, 0, 0, 1, 1, 2, 2, 6, 3, 6, 3, 6]);
+        assert_eq!(game.current_player(), Player::One);
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert!(!game.get_board().has_connected(Player::Two, 4));
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_pop_completing_both_lines_wins_for_popper() {
+        // Popping column 0 also drops player two's piece next to their three along the bottom
+        let mut game = pop_out_game(&[0, 0, 0, 1, 1, 2, 2, 3, 3, 6]);
+        assert_eq!(game.current_player(), Player::One);
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert!(game.get_board().has_connected(Player::One, 4));
+        assert!(game.get_board().has_connected(Player::Two, 4));
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_pop_can_hand_opponent_the_win() {
+        // Player one pops their own piece and lets player two's fall into a line
+        let mut game = pop_out_game(&[0, 0, 6, 1, 6, 2, 5, 3]);
+        assert_eq!(game.current_player(), Player::One);
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!(game.get_winner(), Some(Player::Two));
+    }
+
+    #[test]
+    fn test_undo_and_redo_pop() {
+        let mut game = pop_out_game(&[0, 0, 1]);
+        let before = game.get_board();
+        game.make_move(game.current_player(), &Column::new(1))
+            .unwrap();
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.undo().unwrap();
+        game.undo().unwrap();
+        assert_eq!(game.get_board(), before);
+        game.redo().unwrap();
+        let popped = game.redo().unwrap();
+        assert_eq!(popped.kind, MoveKind::Pop);
+        assert_eq!(
+            game.get_board().get(Row::new(5), Column::new(0)),
+            Some(Player::Two)
+        );
+    }
+
+    // Pop 10 game with the standard board filled row by row from the left, which leaves
+    // diagonal lines for both players
+    fn filled_pop_10_game() -> Game {
+        let mut game = Game::new(GameConfig::default().with_rules(Rules::Pop10));
+        for _ in 0..6 {
+            for col in 0..7 {
+                game.make_move(game.current_player(), &Column::new(col))
+                    .unwrap();
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn test_pop_10_fills_rows_in_order() {
+        let mut game = Game::new(GameConfig::default().with_rules(Rules::Pop10));
+        game.make_move(game.current_player(), &Column::new(3))
+            .unwrap();
+        assert!(matches!(
+            game.make_move(game.current_player(), &Column::new(3)),
+            Err(GameError::RowNotFilled)
+        ));
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(3)),
+            Err(GameError::PopNotAllowed)
+        ));
+
+        let game = filled_pop_10_game();
+        assert!(game.get_board().is_full());
+        assert!(game.check_for_winner().is_some());
+        assert!(!game.is_over());
+        assert_eq!(game.phase(), Phase::Popping);
+        assert_eq!(game.current_player(), Player::One);
+    }
+
+    #[test]
+    fn test_pop_10_collects_pieces_in_lines() {
+        let mut game = filled_pop_10_game();
+        assert!(matches!(
+            game.make_move(game.current_player(), &Column::new(0)),
+            Err(GameError::MustPop)
+        ));
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.pop_out(game.current_player(), &Column::new(2))
+            .unwrap();
+        assert_eq!(game.captured(Player::One), 2);
+        assert_eq!(game.current_player(), Player::One);
+        assert_eq!(game.phase(), Phase::Popping);
+
+        // Not part of a line any more, so it goes back in
+        game.pop_out(game.current_player(), &Column::new(4))
+            .unwrap();
+        assert_eq!(game.captured(Player::One), 2);
+        assert_eq!(game.phase(), Phase::Returning);
+        assert!(matches!(
+            game.pop_out(game.current_player(), &Column::new(6)),
+            Err(GameError::MustReturnPiece)
+        ));
+        game.make_move(game.current_player(), &Column::new(4))
+            .unwrap();
+        assert_eq!(game.phase(), Phase::Popping);
+        assert_eq!(game.current_player(), Player::Two);
+    }
+
+    #[test]
+    fn test_pop_10_undo_restores_captures() {
+        let mut game = filled_pop_10_game();
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        game.pop_out(game.current_player(), &Column::new(2))
+            .unwrap();
+        assert_eq!(game.undo().map(|m| m.kind), Some(MoveKind::Pop));
+        assert_eq!(game.captured(Player::One), 1);
+        assert_eq!(game.current_player(), Player::One);
+        game.redo().unwrap();
+        assert_eq!(game.captured(Player::One), 2);
+    }
+
+    #[test]
+    fn test_pop_10_ten_pieces_win() {
+        let mut game = filled_pop_10_game();
+        game.captured[0] = POP_10_TARGET - 1;
+        game.pop_out(game.current_player(), &Column::new(0))
+            .unwrap();
+        assert_eq!(game.get_winner(), Some(Player::One));
+    }
+
+    #[test]
+    fn test_fixed_blockers() {
+        let config =
+            GameConfig::default().with_blockers(Blockers::fixed(7, [(5, 3), (3, 0)]).unwrap());
+        let mut game = Game::new(config);
+        let board = game.get_board();
+        assert!(board.is_blocker(Row::new(5), Column::new(3)));
+        assert!(board.is_blocker(Row::new(3), Column::new(0)));
+        assert_eq!(
+            game.make_move(game.current_player(), &Column::new(3))
+                .unwrap()
+                .1,
+            Row::new(4)
+        );
+        // Lands on the floating blocker, leaving the cells under it empty
+        assert_eq!(
+            game.make_move(game.current_player(), &Column::new(0))
+                .unwrap()
+                .1,
+            Row::new(2)
+        );
+        game.undo();
+        game.undo();
+        assert!(game.get_board().is_blocker(Row::new(5), Column::new(3)));
+        assert_eq!(game.get_board().get(Row::new(4), Column::new(3)), None);
+    }
+
+    #[test]
+    fn test_random_blockers_are_seeded() {
+        let blockers = Blockers::Random { count: 5, seed: 42 };
+        let config = GameConfig::default().with_blockers(blockers);
+        let board = Game::new(config).get_board();
+        assert_eq!(board, Game::new(config).get_board());
+        let cells: Vec<(Row, Column)> = board
+            .rows()
+            .flat_map(|row| board.columns().map(move |col| (row, col)))
+            .filter(|(row, col)| board.is_blocker(*row, *col))
+            .collect();
+        assert_eq!(cells.len(), 5);
+        // Every blocker sits on the floor or on another blocker
+        for (row, col) in cells {
+            let below = Row::new(usize::from(row) + 1);
+            assert!(usize::from(row) == 5 || board.is_blocker(below, col));
+        }
+        let other = GameConfig::default().with_blockers(Blockers::Random { count: 5, seed: 43 });
+        assert_ne!(board, Game::new(other).get_board());
+    }
+
+    #[test]
+    fn test_blockers_break_lines() {
+        let config = GameConfig::default().with_blockers(Blockers::fixed(7, [(5, 3)]).unwrap());
+        let mut game = Game::new(config);
+        // Player one's fourth piece in a row lands on the blocker instead
+        for col in [0, 6, 1, 6, 2, 5, 3] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        assert_eq!(
+            game.get_board().get(Row::new(4), Column::new(3)),
+            Some(Player::One)
+        );
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn test_winning_lines() {
+        let column: Vec<(Row, Column)> = [5, 4, 3, 2]
+            .map(|row| (Row::new(row), Column::new(0)))
+            .to_vec();
+        for misere in [false, true] {
+            let mut game = Game::new(GameConfig::default().with_misere(misere));
+            for col in [0, 1, 0, 1, 0, 1] {
+                game.make_move(game.current_player(), &Column::new(col))
+                    .unwrap();
+            }
+            assert!(game.winning_lines().is_empty());
+            game.make_move(game.current_player(), &Column::new(0))
+                .unwrap();
+            // In misère the line belongs to the loser
+            assert_eq!(game.winning_lines(), vec![column.clone()]);
+        }
+        let mut game = Game::default();
+        game.surrender(Player::Two);
+        assert!(game.winning_lines().is_empty());
+    }
+
+    #[test]
+    fn test_moves_out_of_turn() {
+        let mut game = Game::new(GameConfig::default().with_rules(Rules::PopOut));
+        assert!(matches!(
+            game.make_move(Player::Two, &Column::new(0)),
+            Err(GameError::NotYourTurn(Player::One))
+        ));
+        assert!(matches!(
+            game.make_move(Player::Spectator, &Column::new(0)),
+            Err(GameError::SpectatorCannotMove)
+        ));
+        game.make_move(Player::One, &Column::new(0)).unwrap();
+        assert!(matches!(
+            game.pop_out(Player::One, &Column::new(0)),
+            Err(GameError::NotYourTurn(Player::Two))
+        ));
+        assert_eq!(game.history().len(), 1);
+        game.surrender(Player::Two);
+        assert!(matches!(
+            game.make_move(Player::One, &Column::new(0)),
+            Err(GameError::GameAlreadyOver)
+        ));
     }
 }
@@ -1,5 +1,13 @@
+pub mod ai;
 pub mod board;
+pub mod book;
+pub mod bot;
+pub mod config;
 pub mod errors;
 pub mod game;
+pub mod mcts;
 pub mod player;
+pub mod score_four;
+pub mod solver;
+pub mod threats;
 pub mod web_socket;
@@ -0,0 +1,257 @@
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::{
+    ai::{Agent, check_supported},
+    board::Column,
+    errors::GameError,
+    game::Game,
+    player::Player,
+};
+
+// How long the search runs before picking a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Iterations(u32),
+    // Uses `std::time::Instant`, which isn't available to wasm builds. Iteration budgets never
+    // read the clock.
+    Time(Duration),
+}
+
+// Upper confidence bound constant, sqrt(2) is the usual choice for rewards in 0..=1
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+// Monte Carlo tree search (UCT) with random playouts. It plays well when given time but
+// makes the occasional human-looking mistake, and is deterministic when seeded with an
+// iteration budget.
+pub struct Mcts {
+    pub budget: Budget,
+    rng: StdRng,
+}
+
+struct Node {
+    // Move that led here from the parent, `None` for the root
+    col: Option<Column>,
+    // Player that made that move
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Column>,
+    visits: u32,
+    // Sum of playout rewards for `player`: 1 for a win and 0.5 for a draw
+    reward: f64,
+}
+
+impl Mcts {
+    pub fn new(budget: Budget) -> Self {
+        Mcts {
+            budget,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn with_seed(budget: Budget, seed: u64) -> Self {
+        Mcts {
+            budget,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Returns the most visited move along with the share of playouts it won, or `None`
+    // if the game is over. Games the engines can't play are an error, see `check_supported`.
+    pub fn search(&mut self, game: &Game) -> Result<Option<(Column, f64)>, GameError> {
+        check_supported(&game.config())?;
+        if game.is_over() {
+            return Ok(None);
+        }
+        let mut tree = vec![Node {
+            col: None,
+            player: other(game.current_player()),
+            parent: None,
+            children: Vec::new(),
+            untried: legal_moves(game),
+            visits: 0,
+            reward: 0.0,
+        }];
+
+        let started = matches!(self.budget, Budget::Time(_)).then(Instant::now);
+        let mut iterations = 0;
+        loop {
+            match self.budget {
+                Budget::Iterations(limit) if iterations >= limit => break,
+                Budget::Time(limit)
+                    if iterations > 0 && started.is_some_and(|t| t.elapsed() >= limit) =>
+                {
+                    break;
+                }
+                _ => {}
+            }
+            self.iterate(&mut tree, game);
+            iterations += 1;
+        }
+
+        Ok(tree[0]
+            .children
+            .iter()
+            .map(|child| &tree[*child])
+            .max_by_key(|child| child.visits)
+            .map(|child| (child.col.unwrap(), child.reward / f64::from(child.visits))))
+    }
+
+    // Any game `check_supported` lets through always has a column to drop into until it is
+    // over, so the moves below can't fail
+
+    fn iterate(&mut self, tree: &mut Vec<Node>, root: &Game) {
+        let mut game = root.clone();
+
+        // Selection
+        let mut node = 0;
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            node = best_child(tree, node);
+            game.make_move(game.current_player(), &tree[node].col.unwrap())
+                .unwrap();
+        }
+
+        // Expansion
+        if !tree[node].untried.is_empty() {
+            let pick = self.rng.gen_range(0..tree[node].untried.len());
+            let col = tree[node].untried.swap_remove(pick);
+            let player = game.current_player();
+            game.make_move(game.current_player(), &col).unwrap();
+            let child = tree.len();
+            tree.push(Node {
+                col: Some(col),
+                player,
+                parent: Some(node),
+                children: Vec::new(),
+                untried: if game.is_over() {
+                    Vec::new()
+                } else {
+                    legal_moves(&game)
+                },
+                visits: 0,
+                reward: 0.0,
+            });
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        // Playout
+        while !game.is_over() {
+            let col = *legal_moves(&game).choose(&mut self.rng).unwrap();
+            game.make_move(game.current_player(), &col).unwrap();
+        }
+
+        // Backpropagation
+        let winner = game.get_winner();
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut tree[index];
+            node.visits += 1;
+            node.reward += match winner {
+                Some(winner) if winner == node.player => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = node.parent;
+        }
+    }
+}
+
+impl Agent for Mcts {
+    fn choose_move(&mut self, game: &Game) -> Option<Column> {
+        self.search(game).ok().flatten().map(|(col, _)| col)
+    }
+}
+
+fn best_child(tree: &[Node], node: usize) -> usize {
+    let parent_visits = f64::from(tree[node].visits).ln();
+    let uct = |child: &Node| {
+        let visits = f64::from(child.visits);
+        child.reward / visits + EXPLORATION * (parent_visits / visits).sqrt()
+    };
+    *tree[node]
+        .children
+        .iter()
+        .max_by(|a, b| uct(&tree[**a]).total_cmp(&uct(&tree[**b])))
+        .unwrap()
+}
+
+fn legal_moves(game: &Game) -> Vec<Column> {
+    let board = game.get_board();
+    board
+        .columns()
+        .filter(|col| !board.is_slot_full(col))
+        .collect()
+}
+
+// The opponent in a two player game, the only kind `check_supported` lets through
+fn other(player: Player) -> Player {
+    match player {
+        Player::One => Player::Two,
+        _ => Player::One,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GameConfig, Rules};
+
+    #[test]
+    fn test_takes_immediate_win() {
+        let game = Game::from_notation("121212").unwrap();
+        let mut mcts = Mcts::with_seed(Budget::Iterations(2000), 1);
+        assert_eq!(mcts.choose_move(&game), Some(Column::new(0)));
+    }
+
+    #[test]
+    fn test_blocks_opponent_win() {
+        let game = Game::from_notation("12131").unwrap();
+        let mut mcts = Mcts::with_seed(Budget::Iterations(3000), 1);
+        assert_eq!(mcts.choose_move(&game), Some(Column::new(0)));
+    }
+
+    #[test]
+    fn test_seeded_search_is_deterministic() {
+        let game = Game::from_notation("4453").unwrap();
+        let first = Mcts::with_seed(Budget::Iterations(300), 42)
+            .search(&game)
+            .unwrap();
+        let second = Mcts::with_seed(Budget::Iterations(300), 42)
+            .search(&game)
+            .unwrap();
+        assert!(first.is_some());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let game = Game::default();
+        let mut mcts = Mcts::with_seed(Budget::Time(Duration::from_millis(20)), 7);
+        assert!(mcts.choose_move(&game).is_some());
+    }
+
+    #[test]
+    fn test_no_move_when_game_over() {
+        let game = Game::from_notation("1212121").unwrap();
+        let mut mcts = Mcts::new(Budget::Iterations(10));
+        assert!(matches!(mcts.search(&game), Ok(None)));
+    }
+
+    #[test]
+    fn test_refuses_pop_rules_and_more_players() {
+        let mut mcts = Mcts::with_seed(Budget::Iterations(10), 1);
+        for config in [
+            GameConfig::default().with_rules(Rules::PopOut),
+            GameConfig::default().with_rules(Rules::Pop10),
+            GameConfig::for_players(3).unwrap(),
+        ] {
+            assert!(matches!(
+                mcts.search(&Game::new(config)),
+                Err(GameError::UnsupportedByBot(_))
+            ));
+        }
+    }
+}
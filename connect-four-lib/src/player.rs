@@ -5,15 +5,59 @@ use serde::{Deserialize, Serialize};
 pub enum Player {
     One,
     Two,
+    // Only seated in three and four player games
+    Three,
+    Four,
     Spectator,
 }
 
+// Most players a game can seat
+pub const MAX_PLAYERS: usize = 4;
+
+impl Player {
+    // Everyone that can hold a seat, in turn order
+    pub const SEATS: [Player; MAX_PLAYERS] =
+        [Player::One, Player::Two, Player::Three, Player::Four];
+
+    // Position in the turn order, starting at 0 for player one
+    pub fn index(self) -> Option<usize> {
+        match self {
+            Player::One => Some(0),
+            Player::Two => Some(1),
+            Player::Three => Some(2),
+            Player::Four => Some(3),
+            Player::Spectator => None,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Player::SEATS.get(index).copied()
+    }
+}
+
 impl std::fmt::Display for Player {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Player::One => write!(f, "Player one"),
             Player::Two => write!(f, "Player two"),
+            Player::Three => write!(f, "Player three"),
+            Player::Four => write!(f, "Player four"),
             Player::Spectator => write!(f, "Spectator"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_round_trip() {
+        for (i, player) in Player::SEATS.into_iter().enumerate() {
+            assert_eq!(player.index(), Some(i));
+            assert_eq!(Player::from_index(i), Some(player));
+        }
+        assert_eq!(Player::Spectator.index(), None);
+        assert_eq!(Player::from_index(MAX_PLAYERS), None);
+    }
+}
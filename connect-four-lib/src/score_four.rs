@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::GameError,
+    game::GameStatus,
+    player::{MAX_PLAYERS, Player},
+};
+
+// Score Four is played on a 4x4 grid of poles, each holding up to four pieces
+pub const SIZE: usize = 4;
+pub const POLES: usize = SIZE * SIZE;
+
+// A pole pieces are dropped down, `x` and `y` from 0 to 3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pole {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Pole {
+    pub const fn new(x: usize, y: usize) -> Self {
+        Pole { x, y }
+    }
+
+    // Every pole, row by row
+    pub fn all() -> impl Iterator<Item = Pole> {
+        (0..SIZE).flat_map(|y| (0..SIZE).map(move |x| Pole::new(x, y)))
+    }
+
+    // Index of the pole in `Pole::all`, used when a pole has to be sent as a number
+    pub fn index(&self) -> usize {
+        self.x + self.y * SIZE
+    }
+
+    pub fn from_index(index: usize) -> Result<Self, GameError> {
+        if index >= POLES {
+            return Err(GameError::OutOfBounds(index));
+        }
+        Ok(Pole::new(index % SIZE, index / SIZE))
+    }
+}
+
+// The 4x4x4 cube as one u64 bitboard per seat. Bit `x + 4 * y + 16 * level` is the cell at
+// `level` on pole (x, y), with level 0 at the bottom.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScoreFourBoard {
+    // Indexed by `Player::index`
+    pieces: [u64; MAX_PLAYERS],
+}
+
+impl ScoreFourBoard {
+    fn bit(pole: Pole, level: usize) -> Option<u64> {
+        if pole.x >= SIZE || pole.y >= SIZE || level >= SIZE {
+            return None;
+        }
+        Some(1 << (pole.index() + level * POLES))
+    }
+
+    fn occupied(&self) -> u64 {
+        self.pieces
+            .iter()
+            .fold(0, |occupied, pieces| occupied | pieces)
+    }
+
+    pub fn get(&self, pole: Pole, level: usize) -> Option<Player> {
+        let bit = Self::bit(pole, level)?;
+        let index = self.pieces.iter().position(|pieces| pieces & bit != 0)?;
+        Player::from_index(index)
+    }
+
+    // Pieces on the pole so far, which is also the level the next piece lands on
+    pub fn pole_height(&self, pole: Pole) -> usize {
+        (0..SIZE)
+            .take_while(|level| self.get(pole, *level).is_some())
+            .count()
+    }
+
+    pub fn is_pole_full(&self, pole: Pole) -> bool {
+        self.pole_height(pole) >= SIZE
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.occupied() == u64::MAX
+    }
+
+    pub fn insert_piece(&mut self, pole: Pole, level: usize, piece: Player) {
+        let (Some(bit), Some(index)) = (Self::bit(pole, level), piece.index()) else {
+            return;
+        };
+        for pieces in &mut self.pieces {
+            *pieces &= !bit;
+        }
+        self.pieces[index] |= bit;
+    }
+
+    pub fn remove_piece(&mut self, pole: Pole, level: usize) {
+        if let Some(bit) = Self::bit(pole, level) {
+            for pieces in &mut self.pieces {
+                *pieces &= !bit;
+            }
+        }
+    }
+
+    // Returns the first player in turn order that has four in a row, if any
+    pub fn winner(&self) -> Option<Player> {
+        Player::SEATS
+            .into_iter()
+            .find(|player| self.has_line(*player))
+    }
+
+    pub fn has_line(&self, player: Player) -> bool {
+        player.index().is_some_and(|index| {
+            let pieces = self.pieces[index];
+            LINES.iter().any(|line| pieces & line == *line)
+        })
+    }
+}
+
+// Every straight line of four cells through the cube: 48 along the axes, 24 diagonals across
+// the faces and slices and 4 through the middle of the cube, 76 in all
+pub const LINES: [u64; 76] = find_lines();
+
+const fn find_lines() -> [u64; 76] {
+    const fn inside(v: isize) -> bool {
+        v >= 0 && v < SIZE as isize
+    }
+    let mut lines = [0; 76];
+    let mut found = 0;
+    let mut direction: isize = 0;
+    while direction < 27 {
+        let (dx, dy, dz) = (direction / 9 - 1, direction / 3 % 3 - 1, direction % 3 - 1);
+        direction += 1;
+        // Only one of each pair of opposite directions
+        if !(dx > 0 || (dx == 0 && (dy > 0 || (dy == 0 && dz > 0)))) {
+            continue;
+        }
+        let mut start = 0;
+        while start < POLES * SIZE {
+            let (x, y, z) = (
+                (start % SIZE) as isize,
+                (start / SIZE % SIZE) as isize,
+                (start / POLES) as isize,
+            );
+            start += 1;
+            let last = SIZE as isize - 1;
+            if !inside(x + last * dx) || !inside(y + last * dy) || !inside(z + last * dz) {
+                continue;
+            }
+            let mut line = 0;
+            let mut i = 0;
+            while i < SIZE as isize {
+                let (x, y, z) = (x + i * dx, y + i * dy, z + i * dz);
+                line |= 1 << (x as usize + y as usize * SIZE + z as usize * POLES);
+                i += 1;
+            }
+            lines[found] = line;
+            found += 1;
+        }
+    }
+    lines
+}
+
+// A two player game of Score Four
+#[derive(Clone, Debug)]
+pub struct ScoreFourGame {
+    board: ScoreFourBoard,
+    status: GameStatus,
+    current_player: Player,
+    // Poles played so far, oldest first
+    history: Vec<Pole>,
+}
+
+impl Default for ScoreFourGame {
+    fn default() -> Self {
+        ScoreFourGame {
+            board: ScoreFourBoard::default(),
+            status: GameStatus::Playing,
+            current_player: Player::One,
+            history: Vec::new(),
+        }
+    }
+}
+
+impl ScoreFourGame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_board(&self) -> ScoreFourBoard {
+        self.board
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.status
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.status != GameStatus::Playing
+    }
+
+    pub fn get_winner(&self) -> Option<Player> {
+        if let GameStatus::Won(winner) = self.status {
+            Some(winner)
+        } else {
+            None
+        }
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.current_player
+    }
+
+    pub fn history(&self) -> &[Pole] {
+        &self.history
+    }
+
+    fn swap_players(&mut self) {
+        self.current_player = match self.current_player {
+            Player::One => Player::Two,
+            _ => Player::One,
+        };
+    }
+
+    // Drops the current player's piece down `pole` and returns the level it landed on
+    pub fn make_move(&mut self, pole: Pole) -> Result<usize, GameError> {
+        if self.is_over() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if pole.x >= SIZE || pole.y >= SIZE {
+            return Err(GameError::OutOfBounds(pole.x.max(pole.y)));
+        }
+        let level = self.board.pole_height(pole);
+        if level >= SIZE {
+            return Err(GameError::ColumnIsFull);
+        }
+        self.board.insert_piece(pole, level, self.current_player);
+        self.history.push(pole);
+        if self.board.has_line(self.current_player) {
+            self.status = GameStatus::Won(self.current_player);
+        } else if self.board.is_full() {
+            self.status = GameStatus::Draw;
+        }
+        self.swap_players();
+        Ok(level)
+    }
+
+    // Takes back the last move, returning the pole it was played on
+    pub fn undo(&mut self) -> Option<Pole> {
+        let pole = self.history.pop()?;
+        let level = self.board.pole_height(pole) - 1;
+        self.board.remove_piece(pole, level);
+        self.swap_players();
+        self.status = GameStatus::Playing;
+        Some(pole)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_76_lines() {
+        assert!(LINES.iter().all(|line| line.count_ones() == 4));
+        let mut unique = LINES.to_vec();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 76);
+    }
+
+    #[test]
+    fn test_pole_index_round_trip() {
+        for pole in Pole::all() {
+            assert_eq!(Pole::from_index(pole.index()).unwrap(), pole);
+        }
+        assert!(matches!(
+            Pole::from_index(POLES),
+            Err(GameError::OutOfBounds(16))
+        ));
+    }
+
+    #[test]
+    fn test_pieces_stack_on_poles() {
+        let mut game = ScoreFourGame::new();
+        let pole = Pole::new(1, 2);
+        for level in 0..SIZE {
+            assert_eq!(game.make_move(pole).unwrap(), level);
+        }
+        assert!(matches!(game.make_move(pole), Err(GameError::ColumnIsFull)));
+        assert!(matches!(
+            game.make_move(Pole::new(4, 0)),
+            Err(GameError::OutOfBounds(4))
+        ));
+        assert!(matches!(
+            game.make_move(Pole::new(1, 6)),
+            Err(GameError::OutOfBounds(6))
+        ));
+        assert_eq!(game.get_board().get(pole, 0), Some(Player::One));
+        assert_eq!(game.get_board().get(pole, 3), Some(Player::Two));
+    }
+
+    #[test]
+    fn test_row_along_the_floor() {
+        let mut game = ScoreFourGame::new();
+        for x in 0..3 {
+            game.make_move(Pole::new(x, 0)).unwrap();
+            game.make_move(Pole::new(x, 3)).unwrap();
+        }
+        game.make_move(Pole::new(3, 0)).unwrap();
+        assert_eq!(game.get_winner(), Some(Player::One));
+        assert!(matches!(
+            game.make_move(Pole::new(2, 2)),
+            Err(GameError::GameAlreadyOver)
+        ));
+        game.undo();
+        assert!(!game.is_over());
+        assert_eq!(game.current_player(), Player::One);
+    }
+
+    #[test]
+    fn test_diagonal_through_the_cube() {
+        let mut board = ScoreFourBoard::default();
+        for i in 0..SIZE {
+            board.insert_piece(Pole::new(i, i), i, Player::Two);
+        }
+        assert_eq!(board.winner(), Some(Player::Two));
+        board.remove_piece(Pole::new(2, 2), 2);
+        assert_eq!(board.winner(), None);
+    }
+}
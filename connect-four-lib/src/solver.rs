@@ -0,0 +1,515 @@
+use crate::{ai::Agent, board::Column, book::Book, game::Game};
+
+const WIDTH: usize = 7;
+const HEIGHT: usize = 6;
+const CELLS: i32 = (WIDTH * HEIGHT) as i32;
+// Lowest and highest score a position can have once the immediate win is ruled out
+const MIN_SCORE: i32 = -CELLS / 2 + 3;
+const MAX_SCORE: i32 = (CELLS + 1) / 2 - 3;
+
+// Same center-first order the negamax engine uses
+const COLUMN_ORDER: [usize; WIDTH] = [3, 2, 4, 1, 5, 0, 6];
+
+// Number of transposition table entries. Keys are stored in full so any size is correct,
+// bigger tables just forget fewer positions.
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 22;
+
+// Exact result of a position with perfect play from both sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solution {
+    // Positive if the player to move wins, negative if they lose and zero for a draw. The
+    // size is one more than the number of pieces the winner still has in hand after their
+    // winning move, so faster wins score higher.
+    pub score: i32,
+    // Moves (from both players) until the game ends, counting the winning move
+    pub plies: u32,
+}
+
+impl Solution {
+    fn new(score: i32, moves_played: i32) -> Self {
+        let plies = if score == 0 {
+            CELLS - moves_played
+        } else {
+            // The winner plays their winning piece with `moves_at_win` pieces on the board
+            let moves_at_win = CELLS + 1 - 2 * score.abs();
+            let winner_parity = if score > 0 {
+                moves_played % 2
+            } else {
+                (moves_played + 1) % 2
+            };
+            let moves_at_win = if moves_at_win % 2 == winner_parity {
+                moves_at_win
+            } else {
+                moves_at_win - 1
+            };
+            moves_at_win - moves_played + 1
+        };
+        Solution {
+            score,
+            plies: plies as u32,
+        }
+    }
+}
+
+// Strong solver for the standard board, any other `GameConfig` is left unsolved. Negamax
+// with alpha-beta pruning inside an iterative null-window search, a transposition table
+// keyed on the position and moves explored in order of how many new threats they create
+pub struct Solver {
+    table: TranspositionTable,
+    book: Option<Book>,
+    explored: u64,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self::with_table_size(DEFAULT_TABLE_SIZE)
+    }
+
+    pub fn with_table_size(size: usize) -> Self {
+        Solver {
+            table: TranspositionTable::new(size.max(1)),
+            book: None,
+            explored: 0,
+        }
+    }
+
+    // Positions found in the book are answered without searching
+    pub fn set_book(&mut self, book: Book) {
+        self.book = Some(book);
+    }
+
+    pub fn book(&self) -> Option<&Book> {
+        self.book.as_ref()
+    }
+
+    // Number of positions searched since the solver was created
+    pub fn explored(&self) -> u64 {
+        self.explored
+    }
+
+    // Solves the position from the point of view of the player to move, or returns `None`
+    // if the game is already over or not played on the standard board
+    pub fn solve(&mut self, game: &Game) -> Option<Solution> {
+        let position = Position::from_game(game)?;
+        let score = self.solve_position(&position);
+        Some(Solution::new(score, position.moves))
+    }
+
+    // Scores every legal move and returns the best one
+    pub fn best_move(&mut self, game: &Game) -> Option<(Column, Solution)> {
+        let position = Position::from_game(game)?;
+        let mut best: Option<(Column, Solution)> = None;
+        for col in COLUMN_ORDER {
+            if !position.can_play(col) {
+                continue;
+            }
+            let score = if position.is_winning_move(col) {
+                (CELLS + 1 - position.moves) / 2
+            } else {
+                let mut next = position;
+                next.play(next.column_move(col));
+                -self.solve_position(&next)
+            };
+            if best.is_none_or(|(_, solution)| score > solution.score) {
+                best = Some((Column::new(col), Solution::new(score, position.moves)));
+            }
+        }
+        best
+    }
+
+    pub(crate) fn solve_position(&mut self, position: &Position) -> i32 {
+        if position.can_win_next() {
+            return (CELLS + 1 - position.moves) / 2;
+        }
+        if let Some(score) = self
+            .book
+            .as_ref()
+            .and_then(|book| book.get_position(position))
+        {
+            return score;
+        }
+        let mut min = -(CELLS - position.moves) / 2;
+        let mut max = (CELLS + 1 - position.moves) / 2;
+
+        // Narrow the score down with null-window searches, probing close to zero first
+        // since most positions are decided a long way from the end
+        while min < max {
+            let mut med = min + (max - min) / 2;
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+            let score = self.negamax(position, med, med + 1);
+            if score <= med {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        min
+    }
+
+    // Requires that the player to move cannot win immediately
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.explored += 1;
+
+        let next = position.non_losing_moves();
+        if next == 0 {
+            return -(CELLS - position.moves) / 2;
+        }
+        if position.moves >= CELLS - 2 {
+            return 0;
+        }
+
+        let min = -(CELLS - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let mut max = (CELLS - 1 - position.moves) / 2;
+        let key = position.key();
+        match self.table.get(key) {
+            0 => {}
+            value if value > MAX_SCORE - MIN_SCORE + 1 => {
+                let min = value + 2 * MIN_SCORE - MAX_SCORE - 2;
+                if alpha < min {
+                    alpha = min;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+            }
+            value => max = value + MIN_SCORE - 1,
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        let mut moves = MoveSorter::default();
+        for col in COLUMN_ORDER.iter().rev() {
+            let mv = next & column_mask(*col);
+            if mv != 0 {
+                moves.add(mv, position.move_score(mv));
+            }
+        }
+        while let Some(mv) = moves.pop() {
+            let mut child = *position;
+            child.play(mv);
+            let score = -self.negamax(&child, -beta, -alpha);
+            if score >= beta {
+                // Lower bounds are stored above the range used for upper bounds
+                self.table.put(key, score + MAX_SCORE - 2 * MIN_SCORE + 2);
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        self.table.put(key, alpha - MIN_SCORE + 1);
+        alpha
+    }
+}
+
+impl Agent for Solver {
+    fn choose_move(&mut self, game: &Game) -> Option<Column> {
+        self.best_move(game).map(|(col, _)| col)
+    }
+}
+
+// Bitboard position in the layout described on `Board`: `current` holds the pieces of the
+// player to move and `mask` holds every piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Position {
+    current: u64,
+    mask: u64,
+    moves: i32,
+}
+
+impl Position {
+    pub(crate) fn from_game(game: &Game) -> Option<Self> {
+        if game.is_over() || !game.config().is_standard() {
+            return None;
+        }
+        let board = game.get_board();
+        let mut position = Position::empty();
+        for col in board.columns() {
+            for row in board.rows() {
+                if let Some(piece) = board.get(row, col) {
+                    let bit =
+                        1 << (usize::from(col) * (HEIGHT + 1) + HEIGHT - 1 - usize::from(row));
+                    position.mask |= bit;
+                    position.moves += 1;
+                    if piece == game.current_player() {
+                        position.current |= bit;
+                    }
+                }
+            }
+        }
+        Some(position)
+    }
+
+    pub(crate) fn moves(&self) -> i32 {
+        self.moves
+    }
+
+    // Unique for every position, the extra bit on top of each column marks its height
+    pub(crate) fn key(&self) -> u64 {
+        self.current + self.mask
+    }
+
+    // The smaller of the key and the key of the mirrored position. Carries never cross
+    // between columns in `key`, so mirroring is just reversing its seven bit groups.
+    pub(crate) fn canonical_key(&self) -> u64 {
+        let key = self.key();
+        let mut mirrored = 0;
+        for col in 0..WIDTH {
+            let bits = (key >> (col * (HEIGHT + 1))) & column_bits();
+            mirrored |= bits << ((WIDTH - 1 - col) * (HEIGHT + 1));
+        }
+        key.min(mirrored)
+    }
+
+    pub(crate) fn empty() -> Self {
+        Position {
+            current: 0,
+            mask: 0,
+            moves: 0,
+        }
+    }
+
+    pub(crate) fn can_play(&self, col: usize) -> bool {
+        self.mask & top_mask(col) == 0
+    }
+
+    pub(crate) fn column_move(&self, col: usize) -> u64 {
+        (self.mask + bottom_mask(col)) & column_mask(col)
+    }
+
+    pub(crate) fn play(&mut self, mv: u64) {
+        self.current ^= self.mask;
+        self.mask |= mv;
+        self.moves += 1;
+    }
+
+    pub(crate) fn is_winning_move(&self, col: usize) -> bool {
+        self.winning_cells() & self.possible() & column_mask(col) != 0
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.winning_cells() & self.possible() != 0
+    }
+
+    fn possible(&self) -> u64 {
+        (self.mask + BOTTOM) & BOARD
+    }
+
+    fn winning_cells(&self) -> u64 {
+        winning_cells(self.current, self.mask)
+    }
+
+    fn opponent_winning_cells(&self) -> u64 {
+        winning_cells(self.current ^ self.mask, self.mask)
+    }
+
+    // Moves that don't hand the opponent an immediate win, either by leaving one of their
+    // threats open or by filling the cell underneath one
+    fn non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_wins = self.opponent_winning_cells();
+        let forced = possible & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // Two threats at once can't both be blocked
+                return 0;
+            }
+            possible = forced;
+        }
+        possible & !(opponent_wins >> 1)
+    }
+
+    // Number of open threats the player to move would have after `mv`
+    fn move_score(&self, mv: u64) -> u32 {
+        winning_cells(self.current | mv, self.mask).count_ones()
+    }
+}
+
+const BOTTOM: u64 = bottom_row();
+const BOARD: u64 = BOTTOM * ((1 << HEIGHT) - 1);
+
+const fn bottom_row() -> u64 {
+    let mut mask = 0;
+    let mut col = 0;
+    while col < WIDTH {
+        mask |= 1 << (col * (HEIGHT + 1));
+        col += 1;
+    }
+    mask
+}
+
+const fn column_bits() -> u64 {
+    (1 << (HEIGHT + 1)) - 1
+}
+
+fn bottom_mask(col: usize) -> u64 {
+    1 << (col * (HEIGHT + 1))
+}
+
+fn top_mask(col: usize) -> u64 {
+    1 << (HEIGHT - 1 + col * (HEIGHT + 1))
+}
+
+fn column_mask(col: usize) -> u64 {
+    ((1 << HEIGHT) - 1) << (col * (HEIGHT + 1))
+}
+
+// Empty cells that would complete a line of four for the owner of `position`
+fn winning_cells(position: u64, mask: u64) -> u64 {
+    // Vertical
+    let mut cells = (position << 1) & (position << 2) & (position << 3);
+
+    // Horizontal and both diagonals, covering the gap being at any of the four spots
+    for shift in [HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+        let pair = (position << shift) & (position << (2 * shift));
+        cells |= pair & (position << (3 * shift));
+        cells |= pair & (position >> shift);
+        let pair = (position >> shift) & (position >> (2 * shift));
+        cells |= pair & (position << shift);
+        cells |= pair & (position >> (3 * shift));
+    }
+
+    cells & (BOARD ^ mask)
+}
+
+struct TranspositionTable {
+    // Each entry packs the full 49 bit key with an 8 bit value, zero meaning empty
+    entries: Vec<u64>,
+}
+
+impl TranspositionTable {
+    fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: vec![0; size],
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    fn put(&mut self, key: u64, value: i32) {
+        let index = self.index(key);
+        self.entries[index] = (key << 8) | value as u64;
+    }
+
+    fn get(&self, key: u64) -> i32 {
+        let entry = self.entries[self.index(key)];
+        if entry >> 8 == key {
+            (entry & 0xff) as i32
+        } else {
+            0
+        }
+    }
+}
+
+// Keeps up to one move per column sorted by score. Moves added later win ties, so adding
+// columns from the edge in keeps the center-first order among equal scores.
+#[derive(Default)]
+struct MoveSorter {
+    entries: [(u64, u32); WIDTH],
+    len: usize,
+}
+
+impl MoveSorter {
+    fn add(&mut self, mv: u64, score: u32) {
+        let mut pos = self.len;
+        self.len += 1;
+        while pos > 0 && self.entries[pos - 1].1 > score {
+            self.entries[pos] = self.entries[pos - 1];
+            pos -= 1;
+        }
+        self.entries[pos] = (mv, score);
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.entries[self.len].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(moves: &str) -> Solution {
+        let game = Game::from_notation(moves).unwrap();
+        Solver::with_table_size(1 << 16).solve(&game).unwrap()
+    }
+
+    #[test]
+    fn test_immediate_win() {
+        // Player one wins with their fourth piece, leaving 17 in hand
+        let solution = solve("121212");
+        assert_eq!(solution.score, 18);
+        assert_eq!(solution.plies, 1);
+    }
+
+    #[test]
+    fn test_forced_loss() {
+        // Player two can't stop the open three along the bottom
+        let solution = solve("33445");
+        assert_eq!(solution.score, -18);
+        assert_eq!(solution.plies, 2);
+    }
+
+    #[test]
+    fn test_game_over_has_no_solution() {
+        let game = Game::from_notation("1212121").unwrap();
+        assert_eq!(Solver::new().solve(&game), None);
+    }
+
+    #[test]
+    fn test_winning_cells() {
+        // Three in a row along the bottom with both ends open
+        let mut position = Position::empty();
+        for col in [2, 0, 3, 0, 4, 6] {
+            position.play(position.column_move(col));
+        }
+        assert!(position.is_winning_move(1));
+        assert!(position.is_winning_move(5));
+        assert!(!position.is_winning_move(0));
+    }
+
+    #[test]
+    fn test_canonical_key_matches_mirror() {
+        let position = Position::from_game(&Game::from_notation("1127").unwrap()).unwrap();
+        let mirrored = Position::from_game(&Game::from_notation("7761").unwrap()).unwrap();
+        assert_ne!(position.key(), mirrored.key());
+        assert_eq!(position.canonical_key(), mirrored.canonical_key());
+    }
+
+    #[test]
+    fn test_move_sorter_prefers_later_on_ties() {
+        let mut sorter = MoveSorter::default();
+        sorter.add(1, 2);
+        sorter.add(2, 5);
+        sorter.add(3, 2);
+        assert_eq!(sorter.pop(), Some(2));
+        assert_eq!(sorter.pop(), Some(3));
+        assert_eq!(sorter.pop(), Some(1));
+        assert_eq!(sorter.pop(), None);
+    }
+}
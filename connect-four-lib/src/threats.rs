@@ -0,0 +1,222 @@
+use crate::{
+    board::{Cell, Column, Row},
+    config::Rules,
+    game::Game,
+    player::Player,
+};
+
+// Which way up a threat is, counting rows from 1 at the bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    Odd,
+    Even,
+}
+
+// An empty cell that would complete a line for `player`, whether or not it can be played yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Threat {
+    pub player: Player,
+    pub row: Row,
+    pub col: Column,
+    pub parity: Parity,
+}
+
+// Columns `player` could drop into to win on the spot if it were their turn. In misère a
+// line loses, so these are the moves that hand the game to the next player instead.
+pub fn winning_moves(game: &Game, player: Player) -> Vec<Column> {
+    let Some(game) = turn_of(game, player) else {
+        return Vec::new();
+    };
+    game.get_board()
+        .columns()
+        .filter(|col| {
+            let mut game = game.clone();
+            game.make_move(player, col).is_ok() && game.get_winner() == Some(player)
+        })
+        .collect()
+}
+
+// Columns the player to move has to drop into to stop someone else from winning next turn
+pub fn must_block(game: &Game) -> Vec<Column> {
+    let current = game.current_player();
+    let mut cols: Vec<Column> = game
+        .players()
+        .filter(|player| *player != current)
+        .flat_map(|player| winning_moves(game, player))
+        .collect();
+    cols.sort_unstable();
+    cols.dedup();
+    cols
+}
+
+// Columns where a drop by the player to move would let someone else win by dropping on top
+// of it
+pub fn poison_columns(game: &Game) -> Vec<Column> {
+    let current = game.current_player();
+    game.get_board()
+        .columns()
+        .filter(|col| {
+            let mut after = game.clone();
+            after.make_move(current, col).is_ok()
+                && !after.is_over()
+                && after
+                    .players()
+                    .filter(|player| *player != current)
+                    .any(|player| winning_moves(&after, player).contains(col))
+        })
+        .collect()
+}
+
+// Every threat on the board for the players still in the game, column by column and
+// bottom up
+pub fn threats(game: &Game) -> Vec<Threat> {
+    let board = game.get_board();
+    let connect = game.config().connect;
+    // Players that already have a line would have a threat in every empty cell
+    let players: Vec<Player> = game
+        .players()
+        .filter(|player| !game.is_eliminated(*player) && !board.has_connected(*player, connect))
+        .collect();
+    let mut threats = Vec::new();
+    for col in board.columns() {
+        for row in board.rows().rev() {
+            if board.cell(row, col) != Cell::Empty {
+                continue;
+            }
+            let parity = if (board.height() - usize::from(row)) % 2 == 1 {
+                Parity::Odd
+            } else {
+                Parity::Even
+            };
+            for player in &players {
+                let mut board = board;
+                board.insert_piece(row, col, *player);
+                if board.has_connected(*player, connect) {
+                    threats.push(Threat {
+                        player: *player,
+                        row,
+                        col,
+                        parity,
+                    });
+                }
+            }
+        }
+    }
+    threats
+}
+
+// Allis's simplest parity rules. When the board has an even number of rows and gets filled
+// up, the first player ends up with the odd rows and the second with the even ones, so an odd
+// threat is good for the first player and an even threat for the second. Returns who the
+// threats favour when only one side has a threat of their own parity. Only makes sense for
+// two players dropping pieces, so anything else gets `None`.
+pub fn parity_advantage(game: &Game) -> Option<Player> {
+    let config = game.config();
+    if config.players != 2
+        || !config.height.is_multiple_of(2)
+        || config.misere
+        || matches!(config.rules, Rules::PopOut | Rules::Pop10)
+    {
+        return None;
+    }
+    let threats = threats(game);
+    let has = |player: Player, parity: Parity| {
+        threats
+            .iter()
+            .any(|threat| threat.player == player && threat.parity == parity)
+    };
+    match (
+        has(Player::One, Parity::Odd),
+        has(Player::Two, Parity::Even),
+    ) {
+        (true, false) => Some(Player::One),
+        (false, true) => Some(Player::Two),
+        _ => None,
+    }
+}
+
+// A copy of `game` where it is `player`'s turn, if they are still playing
+fn turn_of(game: &Game, player: Player) -> Option<Game> {
+    if game.is_over() || game.is_eliminated(player) || !game.players().any(|p| p == player) {
+        return None;
+    }
+    let mut game = game.clone();
+    while game.current_player() != player {
+        game.swap_players();
+    }
+    Some(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    fn play(moves: &[usize]) -> Game {
+        let mut game = Game::default();
+        for col in moves {
+            game.make_move(game.current_player(), &Column::new(*col))
+                .unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn test_winning_moves_and_blocks() {
+        let game = play(&[0, 1, 0, 1, 0]);
+        assert_eq!(winning_moves(&game, Player::One), vec![Column::new(0)]);
+        assert!(winning_moves(&game, Player::Two).is_empty());
+        assert_eq!(must_block(&game), vec![Column::new(0)]);
+        assert!(winning_moves(&game, Player::Spectator).is_empty());
+    }
+
+    #[test]
+    fn test_misere_wins_come_from_the_other_line() {
+        let mut game = Game::new(GameConfig::default().with_misere(true));
+        for col in [0, 1, 0, 1, 0] {
+            game.make_move(game.current_player(), &Column::new(col))
+                .unwrap();
+        }
+        // Player one finishing the column would lose
+        assert!(winning_moves(&game, Player::One).is_empty());
+        assert!(must_block(&game).is_empty());
+    }
+
+    #[test]
+    fn test_poison_columns() {
+        // Player one has three in the second row from the bottom, waiting on column three
+        let game = play(&[1, 0, 0, 2, 2, 6, 1]);
+        assert_eq!(game.current_player(), Player::Two);
+        assert!(must_block(&game).is_empty());
+        assert_eq!(poison_columns(&game), vec![Column::new(3)]);
+        assert_eq!(
+            threats(&game),
+            vec![Threat {
+                player: Player::One,
+                row: Row::new(4),
+                col: Column::new(3),
+                parity: Parity::Even,
+            }]
+        );
+        assert_eq!(parity_advantage(&game), None);
+    }
+
+    #[test]
+    fn test_parity_advantage() {
+        // An odd threat for the first player on the bottom row
+        let game = play(&[0, 6, 1, 6, 2]);
+        assert_eq!(parity_advantage(&game), Some(Player::One));
+        // An even threat for the second player on top of their column
+        let game = play(&[6, 0, 5, 0, 6, 0]);
+        assert_eq!(
+            threats(&game)
+                .iter()
+                .map(|threat| (threat.player, threat.parity))
+                .collect::<Vec<_>>(),
+            vec![(Player::Two, Parity::Even)]
+        );
+        assert_eq!(parity_advantage(&game), Some(Player::Two));
+        let game = Game::new(GameConfig::new(7, 7, 4).unwrap());
+        assert_eq!(parity_advantage(&game), None);
+    }
+}
@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{board::BoardArray, player::Player};
+use crate::{
+    board::BoardArray, bot::Difficulty, config::GameConfig, errors::GameError, player::Player,
+};
+
+// Id of the room every client starts in
+pub const LOBBY: &str = "lobby";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WsMsg {
@@ -14,13 +19,19 @@ pub enum WsMsg {
         active_player: Player,
         // The current state of the board
         game_board: BoardArray,
+        // Size of the board, the length of a winning line and the rules
+        config: GameConfig,
+        // Pieces collected by each seated player in Pop 10, in turn order
+        captured: Vec<u32>,
     },
-    // Join message from client to server
+    // Join message from client to server, asking a spectator's connection for a seat that has
+    // come free. Connections that already have a seat get an error.
     ClientJoin {
         // The ID generated on the client
         id: String,
     },
-    // Leave message
+    // Leave message. From a client it gives up the seat of that client's own connection,
+    // whatever id it names.
     PlayerLeave {
         // Client id on the player that left
         id: String,
@@ -33,8 +44,10 @@ pub enum WsMsg {
         col: usize,
         // Row piece should fall to
         row: usize,
-        // Who becomes the active player after the move
+        // Player that made the move
         active_player: Player,
+        // Player whose turn it is after the move, not always the other player in Pop 10
+        next_player: Player,
     },
     // Move message from client to server
     ClientMove {
@@ -44,12 +57,173 @@ pub enum WsMsg {
         // reach)
         col: usize,
     },
+    // PopOut and Pop 10 move from client to server: pop the client's piece out of the bottom
+    // of `col`
+    ClientPop {
+        // Client id making the move
+        id: String,
+        col: usize,
+    },
+    // Pop move from server to client, everything above the bottom of `col` falls a row
+    ServerPop {
+        // Client id of who made the move
+        id: String,
+        col: usize,
+        // Player that popped their piece
+        active_player: Player,
+        // Player whose turn it is after the pop
+        next_player: Player,
+        // Pieces collected by each seated player in Pop 10, in turn order
+        captured: Vec<u32>,
+    },
+    // Sent only to the client whose message couldn't be read or wasn't allowed
+    Error {
+        code: ErrorCode,
+        // Readable explanation, fit to show to the player as it is
+        message: String,
+        // The message that was turned down, if it could be read at all
+        in_reply_to: Option<Box<WsMsg>>,
+    },
+    // Fog of war: the board as the receiving client is allowed to see it, sent to everyone
+    // after each move. Clients that can't see where a piece went only get this.
+    ServerView {
+        game_board: BoardArray,
+        // Player whose turn it is now
+        next_player: Player,
+    },
     GameOver {
         // Player that won the game
         winner: Player,
+        // (row, col) cells of the lines that ended the game, empty if it wasn't won by a line
+        #[serde(default)]
+        lines: Vec<Vec<(usize, usize)>>,
     },
+    // Sent when the board fills up without a winner
+    GameDraw,
     ClientSurrender {
         player: Player,
     },
+    // A player surrendered in a game with more than two players, the rest play on
+    PlayerEliminated {
+        player: Player,
+        // Player whose turn it is now
+        next_player: Player,
+    },
     NewGame,
+    // Asks the server to fill the empty seat with a computer opponent
+    ClientRequestBot {
+        difficulty: Difficulty,
+    },
+    // Asks the server for a new private room with a game of its own and moves the client into
+    // it. The room gets a short code like "KQ7-PLM" to share with whoever should join.
+    CreateRoom {
+        // Needed on top of the code to join, if set
+        #[serde(default)]
+        password: Option<String>,
+    },
+    // Moves the client into another room, taking a seat if one is free
+    JoinRoom {
+        // Id of the room, or the code of a private room in any case and with or without the
+        // dash
+        room: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    // Sent to a client that entered a room, followed by the join message for its seat there.
+    // Everything sent after it is about the game in this room.
+    RoomJoined {
+        room: String,
+    },
+}
+
+impl WsMsg {
+    // Error message telling a client why `in_reply_to` was turned down
+    pub fn error(error: &GameError, in_reply_to: Option<WsMsg>) -> Self {
+        WsMsg::Error {
+            code: error.into(),
+            message: error.to_string(),
+            in_reply_to: in_reply_to.map(Box::new),
+        }
+    }
+}
+
+// What went wrong in a `WsMsg::Error`, for clients that react to some errors differently
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // The message wasn't valid JSON or not a message the server knows
+    InvalidMessage,
+    ColumnIsFull,
+    OutOfBounds,
+    IllegalMove,
+    InvalidConfig,
+    PopNotAllowed,
+    NotYourPiece,
+    RowNotFilled,
+    MustPop,
+    MustReturnPiece,
+    NotYourTurn,
+    GameAlreadyOver,
+    SpectatorCannotMove,
+    // Computer opponents can't play the room's game
+    UnsupportedByBot,
+    // There is no room with the id the client asked to join
+    RoomNotFound,
+    // The room has a password and the client didn't send it
+    WrongPassword,
+    // The client asked for a seat while it already has one
+    AlreadySeated,
+    // Every seat is taken, so there's no room for a bot
+    NoFreeSeat,
+    // The room already has a bot, and only one plays at a time
+    BotAlreadyPlaying,
+}
+
+impl From<&GameError> for ErrorCode {
+    fn from(value: &GameError) -> Self {
+        match value {
+            GameError::ColumnIsFull => ErrorCode::ColumnIsFull,
+            GameError::OutOfBounds(_) => ErrorCode::OutOfBounds,
+            GameError::InvalidNotation(_) => ErrorCode::InvalidMessage,
+            GameError::IllegalMove { .. } => ErrorCode::IllegalMove,
+            GameError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            GameError::PopNotAllowed => ErrorCode::PopNotAllowed,
+            GameError::NotYourPiece => ErrorCode::NotYourPiece,
+            GameError::RowNotFilled => ErrorCode::RowNotFilled,
+            GameError::MustPop => ErrorCode::MustPop,
+            GameError::MustReturnPiece => ErrorCode::MustReturnPiece,
+            GameError::NotYourTurn(_) => ErrorCode::NotYourTurn,
+            GameError::GameAlreadyOver => ErrorCode::GameAlreadyOver,
+            GameError::SpectatorCannotMove => ErrorCode::SpectatorCannotMove,
+            GameError::UnsupportedByBot(_) => ErrorCode::UnsupportedByBot,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_from_game_error() {
+        let request = WsMsg::ClientMove {
+            id: "abc".to_owned(),
+            col: 3,
+        };
+        let msg = WsMsg::error(&GameError::ColumnIsFull, Some(request));
+        let json = serde_json::to_string(&msg).unwrap();
+        let WsMsg::Error {
+            code,
+            message,
+            in_reply_to,
+        } = serde_json::from_str(&json).unwrap()
+        else {
+            panic!("expected an error, got {json}");
+        };
+        assert_eq!(code, ErrorCode::ColumnIsFull);
+        assert_eq!(message, "column is full");
+        assert!(matches!(
+            in_reply_to.as_deref(),
+            Some(WsMsg::ClientMove { col: 3, .. })
+        ));
+    }
 }
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+
+use connect_four_lib::{game::Game, solver::Solver};
+
+// Every `.txt` file in `tests/data` holds one position per line in the format of the
+// published solver test sets: the move string followed by the expected score. The files
+// there now were scored by a separate brute force search; Pascal Pons' `Test_L3_R1` and
+// `Test_L2_R1` (blog.gamesolver.org) still need checking in next to them.
+#[test]
+fn test_solver_benchmark_sets() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "no benchmark sets in {}", dir.display());
+
+    let mut solver = Solver::new();
+    for file in files {
+        let contents = fs::read_to_string(&file).unwrap();
+        let lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let (moves, expected) = line.split_once(' ').unwrap();
+            let expected: i32 = expected.trim().parse().unwrap();
+            let game = Game::from_notation(moves).unwrap();
+
+            let solution = solver.solve(&game).unwrap();
+            assert_eq!(
+                solution.score,
+                expected,
+                "{moves} in {}",
+                file.file_name().unwrap().to_string_lossy()
+            );
+            let (_, best) = solver.best_move(&game).unwrap();
+            assert_eq!(best, solution, "best move for {moves}");
+        }
+    }
+}
@@ -1,196 +1,47 @@
 use axum::extract::State;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::response::Response;
+use connect_four_lib::ai::Agent;
+use connect_four_lib::board::Column;
+use connect_four_lib::book::Book;
+use connect_four_lib::bot::Bot;
+use connect_four_lib::errors::GameError;
 use connect_four_lib::game::Game;
 use connect_four_lib::player::Player;
+use connect_four_lib::web_socket::ErrorCode;
 use futures_util::SinkExt;
 use futures_util::stream::StreamExt;
 use tokio::sync::mpsc::{self};
 use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::{AppState, Connection, WsMsg};
+use crate::{AppState, BOT_ID, BotSeat, Client, LOBBY, Room, WsMsg, password_matches, room_id};
 
 pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
     ws.on_upgrade(move |socket| websocket_connection(socket, State(state)))
 }
 
-async fn websocket_connection(socket: WebSocket, State(mut state): State<AppState>) {
+async fn websocket_connection(socket: WebSocket, State(state): State<AppState>) {
     info!("socket connected: {:?}", socket);
-    let connections = state.connections.clone();
     let connection_id = uuid::Uuid::new_v4().to_string();
     let (conn_tx, mut conn_rx) = mpsc::unbounded_channel();
 
-    // Register this connection
-    {
-        let mut conns = connections.write().await;
-        conns.insert(
-            connection_id.clone(),
-            Connection {
-                id: connection_id.clone(),
-                tx: conn_tx,
-            },
-        );
-    }
+    connect(&state, &connection_id, conn_tx).await;
 
     let (mut sender, mut receiver) = socket.split();
-    let conns = connections.clone();
-
-    // Assign role to new client
-    let id = Uuid::new_v4().to_string();
-    let player_role = if state.get_player_for_color(Player::One).await.is_none() {
-        Player::One
-    } else if state.get_player_for_color(Player::Two).await.is_none() {
-        Player::Two
-    } else {
-        Player::Spectator
-    };
-    {
-        let mut map = state.player_map.write().await;
-        map.insert(id.clone(), player_role);
-    }
-    {
-        let game = state.game.read().await;
-        let active_player = game.current_player();
-        state
-            .set_player_for_color(player_role, Some(id.clone()))
-            .await;
-        let join_msg = WsMsg::ServerJoin {
-            id: id.clone(),
-            client_player: player_role,
-            active_player,
-            game_board: game.get_board().get_board_array(),
-        };
-        // let json = serde_json::to_value(&join_msg).unwrap();
-        info!("sending message {:?}", join_msg);
-        let conns_guard = conns.read().await;
-        for (_, conn) in conns_guard.iter() {
-            let _ = conn.tx.send(join_msg.clone());
-        }
-    }
+    let recv_connection_id = connection_id.clone();
+    let recv_state = state.clone();
 
     // Handle incoming messages and broadcast to all
     let recv_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
-            if let Ok(msg) = msg {
-                if let Ok(text) = msg.to_text() {
-                    if let Ok(game_msg) = serde_json::from_str::<WsMsg>(text) {
-                        match game_msg {
-                            WsMsg::ClientJoin { id } => {
-                                // Assign color automatically using shared state
-                                let player_role = if state
-                                    .get_player_for_color(Player::One)
-                                    .await
-                                    .is_none()
-                                {
-                                    Player::One
-                                } else if state.get_player_for_color(Player::Two).await.is_none() {
-                                    Player::Two
-                                } else {
-                                    Player::Spectator
-                                };
-                                {
-                                    let mut map = state.player_map.write().await;
-                                    map.insert(id.clone(), player_role);
-                                }
-                                let game = state.game.read().await;
-                                let active_player = game.current_player();
-                                state
-                                    .set_player_for_color(player_role, Some(id.clone()))
-                                    .await;
-                                let join_msg = WsMsg::ServerJoin {
-                                    id,
-                                    client_player: player_role,
-                                    active_player,
-                                    game_board: game.get_board().get_board_array(),
-                                };
-                                // let json = serde_json::to_value(&join_msg).unwrap();
-                                info!("sending message {:?}", join_msg);
-                                let conns_guard = conns.read().await;
-                                for (_, conn) in conns_guard.iter() {
-                                    let _ = conn.tx.send(join_msg.clone());
-                                }
-                            }
-                            WsMsg::ClientMove { id, col } => {
-                                info!("making move on col {}", col);
-                                let mut game = state.game.write().await;
-                                let player_that_made_move = game.current_player();
-                                match game.make_move(&col.into()) {
-                                    Ok((col, row)) => {
-                                        let msg = WsMsg::ServerMove {
-                                            id,
-                                            col: col.into(),
-                                            row: row.into(),
-                                            active_player: player_that_made_move,
-                                        };
-                                        info!("sending message {:?}", msg);
-                                        let conns_guard = conns.read().await;
-                                        for (_, conn) in conns_guard.iter() {
-                                            let _ = conn.tx.send(msg.clone());
-                                        }
-                                    }
-                                    Err(_e) => {
-                                        // TODO: Handle server error messages
-                                        // tracing::error!("Failed to make move: {:?}", e);
-                                        // info!("sending message {:?}", msg);
-                                        // let conns_guard = conns.read().await;
-                                        // for (_, conn) in conns_guard.iter() {
-                                        //     let _ = conn.tx.send(e.clone());
-                                        // }
-                                    }
-                                }
-                                if game.is_over() {
-                                    let winner = game.get_winner().unwrap();
-                                    info!("{} wins!", winner);
-                                    let msg = WsMsg::GameOver { winner };
-                                    info!("sending message {:?}", msg);
-                                    let conns_guard = conns.read().await;
-                                    for (_, conn) in conns_guard.iter() {
-                                        let _ = conn.tx.send(msg.clone());
-                                    }
-                                }
-                            }
-                            WsMsg::PlayerLeave { id } => {
-                                info!("player {} has left the game", id);
-                                let mut map = state.player_map.write().await;
-                                if let Some(color) = map.remove(&id) {
-                                    state.set_player_for_color(color, None).await;
-                                }
-                            }
-                            WsMsg::ClientSurrender { player } => {
-                                info!("player {} has surrendered", player);
-                                let mut game = state.game.write().await;
-                                game.surrender(player);
-                                if let Some(winner) = game.get_winner() {
-                                    let msg = WsMsg::GameOver { winner };
-                                    info!("sending message {:?}", msg);
-                                    let conns_guard = conns.read().await;
-                                    for (_, conn) in conns_guard.iter() {
-                                        let _ = conn.tx.send(msg.clone());
-                                    }
-                                }
-                            }
-                            WsMsg::NewGame => {
-                                info!("making new game");
-                                let mut game = state.game.write().await;
-                                *game = Game::new();
-                                let msg = WsMsg::NewGame;
-                                info!("sending message {:?}", msg);
-                                let conns_guard = conns.read().await;
-                                for (_, conn) in conns_guard.iter() {
-                                    let _ = conn.tx.send(msg.clone());
-                                }
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        error!("something went wrong getting message");
-                    }
-                }
+            if let Ok(msg) = msg
+                && let Ok(text) = msg.to_text()
+            {
+                handle_message(&recv_state, &recv_connection_id, text).await;
             }
         }
         info!("connection closed?");
-        state.leave_player(id).await;
     });
 
     // Handle outgoing messages
@@ -211,6 +62,769 @@ async fn websocket_connection(socket: WebSocket, State(mut state): State<AppStat
     }
 
     // Clean up
-    let mut conns = connections.write().await;
-    conns.remove(&connection_id);
+    state.leave_room(&connection_id).await;
+    state.clients.write().await.remove(&connection_id);
+}
+
+// Registers a new connection and starts it off in the lobby
+async fn connect(state: &AppState, connection_id: &str, tx: mpsc::UnboundedSender<WsMsg>) {
+    state.clients.write().await.insert(
+        connection_id.to_owned(),
+        Client {
+            tx,
+            id: Uuid::new_v4().to_string(),
+            room: None,
+        },
+    );
+    let lobby = state
+        .room(LOBBY)
+        .await
+        .unwrap_or_else(|| Room::new(LOBBY.to_owned(), state.config));
+    state.enter_room(connection_id, lobby).await;
+}
+
+// Handles one message from the client on `connection_id`. Room messages are handled here and
+// everything else goes to the room the client is in. Messages that can't be read and moves
+// that aren't allowed are answered with an error sent only to that client.
+async fn handle_message(state: &AppState, connection_id: &str, text: &str) {
+    let game_msg = match serde_json::from_str::<WsMsg>(text) {
+        Ok(game_msg) => game_msg,
+        Err(e) => {
+            error!("something went wrong getting message: {}", e);
+            let msg = WsMsg::Error {
+                code: ErrorCode::InvalidMessage,
+                message: format!("the server couldn't read the message: {e}"),
+                in_reply_to: None,
+            };
+            state.send_to(connection_id, msg).await;
+            return;
+        }
+    };
+    match game_msg {
+        WsMsg::CreateRoom { password } => {
+            let room = state.create_room(password).await;
+            info!("{} created room {}", connection_id, room.id);
+            state.enter_room(connection_id, room).await;
+        }
+        WsMsg::JoinRoom {
+            ref room,
+            ref password,
+        } => match state.room(&room_id(room)).await {
+            Some(room)
+                if room.password.as_deref().is_some_and(|expected| {
+                    !password_matches(expected, password.as_deref().unwrap_or_default())
+                }) =>
+            {
+                info!(
+                    "{} gave the wrong password for room {}",
+                    connection_id, room.id
+                );
+                let msg = WsMsg::Error {
+                    code: ErrorCode::WrongPassword,
+                    message: format!("room {} needs the right password", room.id),
+                    in_reply_to: Some(Box::new(game_msg.clone())),
+                };
+                state.send_to(connection_id, msg).await;
+            }
+            Some(room) => state.enter_room(connection_id, room).await,
+            None => {
+                info!("{} tried to join missing room {}", connection_id, room);
+                let msg = WsMsg::Error {
+                    code: ErrorCode::RoomNotFound,
+                    message: format!("there is no room {room}"),
+                    in_reply_to: Some(Box::new(game_msg.clone())),
+                };
+                state.send_to(connection_id, msg).await;
+            }
+        },
+        WsMsg::ClientJoin { .. } => {
+            let Some(room) = state.room_of(connection_id).await else {
+                return;
+            };
+            if room.player_on(connection_id).await != Player::Spectator {
+                info!("{} already has a seat", connection_id);
+                let msg = WsMsg::Error {
+                    code: ErrorCode::AlreadySeated,
+                    message: "you already have a seat".to_owned(),
+                    in_reply_to: Some(Box::new(game_msg.clone())),
+                };
+                state.send_to(connection_id, msg).await;
+                return;
+            }
+            // Seats are only handed out on the way into a room, so spectators take one that
+            // has come free by coming in again
+            state.enter_room(connection_id, room).await;
+        }
+        game_msg => {
+            let Some(room) = state.room_of(connection_id).await else {
+                info!("{} isn't in a room", connection_id);
+                return;
+            };
+            handle_room_message(state, &room, connection_id, game_msg).await;
+        }
+    }
+}
+
+// Handles a game message from the client on `connection_id`, played in `room`
+async fn handle_room_message(state: &AppState, room: &Room, connection_id: &str, game_msg: WsMsg) {
+    let request = game_msg.clone();
+    match game_msg {
+        WsMsg::ClientMove { id, col } => {
+            info!("making move on col {}", col);
+            let Some(col) = column(room, connection_id, col, &request).await else {
+                return;
+            };
+            let player_that_made_move = room.player_on(connection_id).await;
+            let mut game = room.game.write().await;
+            match game.make_move(player_that_made_move, &col) {
+                Ok((col, row)) => {
+                    let msg = WsMsg::ServerMove {
+                        id,
+                        col: col.into(),
+                        row: row.into(),
+                        active_player: player_that_made_move,
+                        next_player: game.current_player(),
+                    };
+                    broadcast_move(room, &game, msg, |player| {
+                        game.view_for(player).get(row, col).is_some()
+                    })
+                    .await;
+                }
+                Err(error) => {
+                    info!("move by {} rejected: {}", player_that_made_move, error);
+                    room.send_to(connection_id, WsMsg::error(&error, Some(request)))
+                        .await;
+                    return;
+                }
+            }
+            if let Some(msg) = game_over_message(&game) {
+                room.broadcast(msg).await;
+            }
+            drop(game);
+            play_bot_turn(room).await;
+        }
+        WsMsg::ClientPop { id, col } => {
+            info!("popping piece out of col {}", col);
+            let Some(col) = column(room, connection_id, col, &request).await else {
+                return;
+            };
+            let player_that_made_move = room.player_on(connection_id).await;
+            let mut game = room.game.write().await;
+            // Who could see the piece before it went
+            let before = game.clone();
+            match game.pop_out(player_that_made_move, &col) {
+                Ok((col, row)) => {
+                    let msg = WsMsg::ServerPop {
+                        id,
+                        col: col.into(),
+                        active_player: player_that_made_move,
+                        next_player: game.current_player(),
+                        captured: captured(&game),
+                    };
+                    broadcast_move(room, &game, msg, |player| {
+                        before.view_for(player).get(row, col).is_some()
+                    })
+                    .await;
+                }
+                Err(error) => {
+                    info!("pop by {} rejected: {}", player_that_made_move, error);
+                    room.send_to(connection_id, WsMsg::error(&error, Some(request)))
+                        .await;
+                    return;
+                }
+            }
+            if let Some(msg) = game_over_message(&game) {
+                room.broadcast(msg).await;
+            }
+            drop(game);
+            play_bot_turn(room).await;
+        }
+        WsMsg::PlayerLeave { .. } => {
+            // Whoever the message names, clients can only give up their own seat
+            info!("{} has left their seat", connection_id);
+            room.leave_seat(connection_id).await;
+        }
+        WsMsg::ClientSurrender { player: claimed } => {
+            // Clients can only surrender their own seat
+            let player = room.player_on(connection_id).await;
+            if player == Player::Spectator {
+                info!("spectator tried to surrender as {}", claimed);
+                let error = GameError::SpectatorCannotMove;
+                room.send_to(connection_id, WsMsg::error(&error, Some(request)))
+                    .await;
+                return;
+            }
+            info!("player {} has surrendered", player);
+            let mut game = room.game.write().await;
+            game.surrender(player);
+            broadcast_views(room, &game).await;
+            if let Some(winner) = game.get_winner() {
+                room.broadcast(WsMsg::GameOver {
+                    winner,
+                    lines: winning_lines(&game),
+                })
+                .await;
+            } else if game.is_eliminated(player) {
+                // Everyone else keeps playing
+                room.broadcast(WsMsg::PlayerEliminated {
+                    player,
+                    next_player: game.current_player(),
+                })
+                .await;
+            }
+            drop(game);
+            play_bot_turn(room).await;
+        }
+        WsMsg::NewGame => {
+            if room.player_on(connection_id).await == Player::Spectator {
+                info!("spectator tried to start a new game");
+                let error = GameError::SpectatorCannotMove;
+                room.send_to(connection_id, WsMsg::error(&error, Some(request)))
+                    .await;
+                return;
+            }
+            info!("making new game");
+            let mut game = room.game.write().await;
+            *game = Game::new(room.config);
+            room.broadcast(WsMsg::NewGame).await;
+            drop(game);
+            play_bot_turn(room).await;
+        }
+        WsMsg::ClientRequestBot { difficulty } => {
+            let mut bot = match Bot::new(difficulty, &room.config) {
+                Ok(bot) => bot,
+                Err(error) => {
+                    info!("no {} bot: {}", difficulty, error);
+                    room.send_to(connection_id, WsMsg::error(&error, Some(request)))
+                        .await;
+                    return;
+                }
+            };
+            if let Some(book) = &state.book {
+                bot.set_book(Book::clone(book));
+            }
+            // Held until the bot is in place, so two requests can't both seat one
+            let mut bot_seat = room.bot.lock().await;
+            if bot_seat.is_some() {
+                info!("the room already has a bot");
+                let msg = WsMsg::Error {
+                    code: ErrorCode::BotAlreadyPlaying,
+                    message: "there's already a bot playing".to_owned(),
+                    in_reply_to: Some(Box::new(request)),
+                };
+                room.send_to(connection_id, msg).await;
+                return;
+            }
+            let seat = room.take_seat(BOT_ID).await;
+            if seat == Player::Spectator {
+                info!("no free seat for a {} bot", difficulty);
+                let msg = WsMsg::Error {
+                    code: ErrorCode::NoFreeSeat,
+                    message: "there's no free seat for a bot".to_owned(),
+                    in_reply_to: Some(Box::new(request)),
+                };
+                room.send_to(connection_id, msg).await;
+                return;
+            }
+            info!("adding {} bot as {}", difficulty, seat);
+            *bot_seat = Some(BotSeat { player: seat, bot });
+            drop(bot_seat);
+            {
+                let game = room.game.read().await;
+                room.broadcast_each(|recipient| Some(join_message(&game, BOT_ID, seat, recipient)))
+                    .await;
+            }
+            play_bot_turn(room).await;
+        }
+        _ => {}
+    }
+}
+
+// Column sent by a client in `request`, or `None` after telling the client it can't be on
+// any board
+async fn column(room: &Room, connection_id: &str, col: usize, request: &WsMsg) -> Option<Column> {
+    match Column::try_from(col) {
+        Ok(col) => Some(col),
+        Err(error) => {
+            info!("rejected column {}: {}", col, error);
+            room.send_to(connection_id, WsMsg::error(&error, Some(request.clone())))
+                .await;
+            None
+        }
+    }
+}
+
+fn game_over_message(game: &Game) -> Option<WsMsg> {
+    if !game.is_over() {
+        return None;
+    }
+    match game.get_winner() {
+        Some(winner) => {
+            if game.config().misere {
+                info!("{} wins, their opponent made a line in misère!", winner);
+            } else {
+                info!("{} wins!", winner);
+            }
+            Some(WsMsg::GameOver {
+                winner,
+                lines: winning_lines(game),
+            })
+        }
+        None => {
+            info!("game ended in a draw");
+            Some(WsMsg::GameDraw)
+        }
+    }
+}
+
+// Join message for a client sitting as `client_player`, with the board as `recipient` sees it
+pub(crate) fn join_message(
+    game: &Game,
+    id: &str,
+    client_player: Player,
+    recipient: Player,
+) -> WsMsg {
+    WsMsg::ServerJoin {
+        id: id.to_owned(),
+        client_player,
+        active_player: game.current_player(),
+        game_board: game.view_for(recipient).get_board_array(),
+        config: game.config(),
+        captured: captured(game),
+    }
+}
+
+// Sends a move to everyone, or in fog of war only to the players that `sees` the piece.
+// Everyone is then sent what they can see of the board, which covers the moves they weren't
+// told about and any pieces that came into view.
+async fn broadcast_move(room: &Room, game: &Game, msg: WsMsg, sees: impl Fn(Player) -> bool) {
+    let fog_of_war = game.config().fog_of_war;
+    room.broadcast_each(|player| (!fog_of_war || sees(player)).then(|| msg.clone()))
+        .await;
+    broadcast_views(room, game).await;
+}
+
+// Fog of war: sends everyone the board as they see it. Once the game is over this reveals
+// the whole board, winning lines included.
+async fn broadcast_views(room: &Room, game: &Game) {
+    if !game.config().fog_of_war {
+        return;
+    }
+    room.broadcast_each(|player| {
+        Some(WsMsg::ServerView {
+            game_board: game.view_for(player).get_board_array(),
+            next_player: game.current_player(),
+        })
+    })
+    .await;
+}
+
+// Pieces collected by each seated player in Pop 10, as sent to clients
+fn captured(game: &Game) -> Vec<u32> {
+    game.players().map(|player| game.captured(player)).collect()
+}
+
+// Cells of the lines that ended the game, as (row, col) for clients
+fn winning_lines(game: &Game) -> Vec<Vec<(usize, usize)>> {
+    game.winning_lines()
+        .into_iter()
+        .map(|line| {
+            line.into_iter()
+                .map(|(row, col)| (row.into(), col.into()))
+                .collect()
+        })
+        .collect()
+}
+
+// Makes the computer opponent's move if it is its turn
+async fn play_bot_turn(room: &Room) {
+    let mut bot_seat = room.bot.lock().await;
+    let Some(seat) = bot_seat.as_mut() else {
+        return;
+    };
+    let mut game = room.game.write().await;
+    if game.is_over() || game.current_player() != seat.player {
+        return;
+    }
+    // Searching can take a while on the harder difficulties
+    let Some(col) = tokio::task::block_in_place(|| seat.bot.choose_move(&game)) else {
+        return;
+    };
+    info!("bot making move on col {:?}", col);
+    match game.make_move(seat.player, &col) {
+        Ok((col, row)) => {
+            let msg = WsMsg::ServerMove {
+                id: BOT_ID.to_owned(),
+                col: col.into(),
+                row: row.into(),
+                active_player: seat.player,
+                next_player: game.current_player(),
+            };
+            broadcast_move(room, &game, msg, |player| {
+                game.view_for(player).get(row, col).is_some()
+            })
+            .await;
+        }
+        Err(e) => error!("bot made an illegal move: {}", e),
+    }
+    if let Some(msg) = game_over_message(&game) {
+        room.broadcast(msg).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CODE_CHARS;
+    use connect_four_lib::config::GameConfig;
+    use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+    use serde_json::{Value, json};
+
+    // Any JSON value, nested up to `depth` deep
+    fn arbitrary_json(rng: &mut StdRng, depth: u32) -> Value {
+        let kinds = if depth == 0 { 5 } else { 7 };
+        match rng.gen_range(0..kinds) {
+            0 => Value::Null,
+            1 => json!(rng.r#gen::<bool>()),
+            2 => json!(
+                [0, 6, 7, 63, 64, 127, 128, u64::MAX, rng.r#gen::<u64>()]
+                    .choose(rng)
+                    .copied()
+            ),
+            3 => json!([-1.0, 0.5, 1e300, f64::MIN].choose(rng).copied()),
+            4 => json!(
+                ["", "One", "Spectator", "NewGame", "Beginner", "{", "\u{0}"]
+                    .choose(rng)
+                    .copied()
+            ),
+            5 => Value::Array(
+                (0..rng.gen_range(0..4))
+                    .map(|_| arbitrary_json(rng, depth - 1))
+                    .collect(),
+            ),
+            _ => {
+                let keys = ["id", "col", "row", "player", "ClientMove", "ClientPop"];
+                Value::Object(
+                    (0..rng.gen_range(0..4))
+                        .map(|_| {
+                            let key = keys.choose(rng).copied().unwrap_or_default();
+                            (key.to_owned(), arbitrary_json(rng, depth - 1))
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    // Something shaped like a client message with arbitrary values in it, or anything at all
+    fn arbitrary_message(rng: &mut StdRng) -> String {
+        let value = match rng.gen_range(0..10) {
+            0 => {
+                json!({"ClientMove": {"id": arbitrary_json(rng, 1), "col": arbitrary_json(rng, 1)}})
+            }
+            1 => json!({"ClientMove": {"id": "fuzz", "col": rng.gen_range(0..10)}}),
+            2 => json!({"ClientPop": {"id": "fuzz", "col": arbitrary_json(rng, 1)}}),
+            3 => json!({"ClientSurrender": {"player": arbitrary_json(rng, 1)}}),
+            4 => json!({"ClientJoin": {"id": arbitrary_json(rng, 1)}}),
+            5 => json!(["NewGame", "PlayerLeave", "GameDraw"].choose(rng).copied()),
+            6 => json!({"CreateRoom": {"password": arbitrary_json(rng, 1)}}),
+            7 => json!({"JoinRoom": {"room": (["lobby", "nowhere"].choose(rng).copied())}}),
+            _ => arbitrary_json(rng, 3),
+        };
+        let text = value.to_string();
+        // Sometimes cut it short, which leaves invalid JSON
+        if rng.gen_bool(0.1) {
+            let end = rng.gen_range(0..=text.len());
+            return text.get(..end).unwrap_or_default().to_owned();
+        }
+        text
+    }
+
+    async fn join(state: &AppState, connection_id: &str) -> mpsc::UnboundedReceiver<WsMsg> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        connect(state, connection_id, tx).await;
+        rx
+    }
+
+    // Throws away everything sent so far
+    fn skip_messages(receivers: &mut [&mut mpsc::UnboundedReceiver<WsMsg>]) {
+        for rx in receivers {
+            while rx.try_recv().is_ok() {}
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_arbitrary_messages_never_panic() {
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        join(&state, "yellow").await;
+        join(&state, "watcher").await;
+        let mut rng = StdRng::seed_from_u64(22);
+        for _ in 0..2000 {
+            let connection_id = ["red", "yellow", "watcher", "gone"]
+                .choose(&mut rng)
+                .copied()
+                .unwrap_or_default();
+            handle_message(&state, connection_id, &arbitrary_message(&mut rng)).await;
+            for room in state.rooms.read().await.values() {
+                assert!(room.game.read().await.history().len() <= 42);
+                assert!(!room.connections.read().await.is_empty());
+            }
+        }
+        // Out of range columns are turned away before they reach the game
+        handle_message(
+            &state,
+            "red",
+            &json!({"ClientMove": {"id": "fuzz", "col": usize::MAX}}).to_string(),
+        )
+        .await;
+        let mut rejected = false;
+        while let Ok(msg) = red.try_recv() {
+            rejected |= matches!(
+                msg,
+                WsMsg::Error {
+                    code: ErrorCode::OutOfBounds,
+                    ..
+                }
+            );
+        }
+        assert!(rejected);
+    }
+
+    #[tokio::test]
+    async fn test_errors_only_go_to_the_sender() {
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        let mut yellow = join(&state, "yellow").await;
+        skip_messages(&mut [&mut red, &mut yellow]);
+        handle_message(&state, "yellow", r#"{"ClientMove":{"id":"y","col":3}}"#).await;
+        handle_message(&state, "yellow", "not json").await;
+        let Ok(WsMsg::Error {
+            code, in_reply_to, ..
+        }) = yellow.try_recv()
+        else {
+            panic!("expected an error for moving out of turn");
+        };
+        assert_eq!(code, ErrorCode::NotYourTurn);
+        assert!(matches!(
+            in_reply_to.as_deref(),
+            Some(WsMsg::ClientMove { col: 3, .. })
+        ));
+        assert!(matches!(
+            yellow.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::InvalidMessage,
+                in_reply_to: None,
+                ..
+            })
+        ));
+        assert!(red.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rooms() {
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        let mut yellow = join(&state, "yellow").await;
+        let mut watcher = join(&state, "watcher").await;
+        skip_messages(&mut [&mut red, &mut yellow, &mut watcher]);
+
+        handle_message(&state, "red", r#"{"CreateRoom":{}}"#).await;
+        let Ok(WsMsg::RoomJoined { room }) = red.try_recv() else {
+            panic!("expected to be moved into the new room");
+        };
+        assert!(matches!(
+            red.try_recv(),
+            Ok(WsMsg::ServerJoin {
+                client_player: Player::One,
+                ..
+            })
+        ));
+        // Nobody left behind hears about the new room
+        assert!(yellow.try_recv().is_err());
+
+        let join_room = json!({"JoinRoom": {"room": room}}).to_string();
+        handle_message(&state, "yellow", &join_room).await;
+        assert!(matches!(yellow.try_recv(), Ok(WsMsg::RoomJoined { .. })));
+        assert!(matches!(
+            yellow.try_recv(),
+            Ok(WsMsg::ServerJoin {
+                client_player: Player::Two,
+                ..
+            })
+        ));
+        skip_messages(&mut [&mut red]);
+
+        // Moves only reach the room they were made in
+        handle_message(&state, "red", r#"{"ClientMove":{"id":"r","col":3}}"#).await;
+        assert!(matches!(
+            yellow.try_recv(),
+            Ok(WsMsg::ServerMove { col: 3, .. })
+        ));
+        assert!(watcher.try_recv().is_err());
+        let lobby = state.room(LOBBY).await.unwrap();
+        assert!(lobby.game.read().await.history().is_empty());
+        assert!(lobby.seats.read().await.iter().all(Option::is_none));
+
+        handle_message(&state, "watcher", r#"{"JoinRoom":{"room":"nowhere"}}"#).await;
+        assert!(matches!(
+            watcher.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::RoomNotFound,
+                ..
+            })
+        ));
+
+        // Rooms are closed when the last connection leaves
+        state.leave_room("watcher").await;
+        assert!(state.room(LOBBY).await.is_none());
+        state.leave_room("red").await;
+        state.leave_room("yellow").await;
+        assert!(state.rooms.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_private_rooms() {
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        let mut yellow = join(&state, "yellow").await;
+        skip_messages(&mut [&mut red, &mut yellow]);
+
+        handle_message(&state, "red", r#"{"CreateRoom":{"password":"hunter2"}}"#).await;
+        let Ok(WsMsg::RoomJoined { room: code }) = red.try_recv() else {
+            panic!("expected to be moved into the new room");
+        };
+        assert_eq!(code.len(), 7);
+        assert_eq!(code.as_bytes()[3], b'-');
+        assert!(code.bytes().all(|c| c == b'-' || CODE_CHARS.contains(&c)));
+
+        // The code works in any case without the dash, but not without the password
+        let typed = code.replace('-', "").to_lowercase();
+        let join_room = json!({"JoinRoom": {"room": typed}}).to_string();
+        handle_message(&state, "yellow", &join_room).await;
+        assert!(matches!(
+            yellow.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::WrongPassword,
+                ..
+            })
+        ));
+        let join_room = json!({"JoinRoom": {"room": typed, "password": "hunter2"}}).to_string();
+        handle_message(&state, "yellow", &join_room).await;
+        assert!(matches!(yellow.try_recv(), Ok(WsMsg::RoomJoined { room }) if room == code));
+        let room = state.room(&code).await.unwrap();
+        assert!(room.seats.read().await.iter().all(Option::is_some));
+        assert!(password_matches("hunter2", "hunter2"));
+        for wrong in ["", "hunter", "hunter22", "Hunter2"] {
+            assert!(!password_matches("hunter2", wrong));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seats_belong_to_connections() {
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        join(&state, "yellow").await;
+        let mut watcher = join(&state, "watcher").await;
+        let red_id = state.clients.read().await["red"].id.clone();
+        skip_messages(&mut [&mut red, &mut watcher]);
+        let lobby = state.room(LOBBY).await.unwrap();
+
+        // Naming someone else's id doesn't free their seat or hand it over
+        let leave = json!({"PlayerLeave": {"id": red_id}}).to_string();
+        handle_message(&state, "watcher", &leave).await;
+        handle_message(&state, "watcher", r#"{"ClientJoin":{"id":"w"}}"#).await;
+        assert_eq!(lobby.player_on("red").await, Player::One);
+        assert_eq!(lobby.player_on("watcher").await, Player::Spectator);
+        skip_messages(&mut [&mut red, &mut watcher]);
+        handle_message(&state, "watcher", r#""NewGame""#).await;
+        assert!(matches!(
+            watcher.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::SpectatorCannotMove,
+                ..
+            })
+        ));
+
+        handle_message(&state, "red", r#"{"ClientJoin":{"id":"r"}}"#).await;
+        assert!(matches!(
+            red.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::AlreadySeated,
+                ..
+            })
+        ));
+
+        // A seat given up by its own client can be taken
+        handle_message(&state, "red", r#"{"PlayerLeave":{"id":"anyone"}}"#).await;
+        assert_eq!(lobby.player_on("red").await, Player::Spectator);
+        handle_message(&state, "watcher", r#"{"ClientJoin":{"id":"w"}}"#).await;
+        assert_eq!(lobby.player_on("watcher").await, Player::One);
+        assert_eq!(
+            lobby.seats.read().await.iter().flatten().count(),
+            2,
+            "nobody holds two seats"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bot_requests_are_answered() {
+        let config = GameConfig::default().with_rules(connect_four_lib::config::Rules::Pop10);
+        let state = AppState::new(config);
+        let mut red = join(&state, "red").await;
+        skip_messages(&mut [&mut red]);
+        handle_message(
+            &state,
+            "red",
+            r#"{"ClientRequestBot":{"difficulty":"Casual"}}"#,
+        )
+        .await;
+        assert!(matches!(
+            red.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::UnsupportedByBot,
+                ..
+            })
+        ));
+        let lobby = state.room(LOBBY).await.unwrap();
+        assert!(lobby.bot.lock().await.is_none());
+        assert_eq!(lobby.seats.read().await.iter().flatten().count(), 1);
+
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        let mut yellow = join(&state, "yellow").await;
+        skip_messages(&mut [&mut red, &mut yellow]);
+        handle_message(
+            &state,
+            "red",
+            r#"{"ClientRequestBot":{"difficulty":"Casual"}}"#,
+        )
+        .await;
+        assert!(matches!(
+            red.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::NoFreeSeat,
+                ..
+            })
+        ));
+
+        // A second bot is refused even once a seat has come free for it
+        let state = AppState::new(GameConfig::default());
+        let mut red = join(&state, "red").await;
+        let request = r#"{"ClientRequestBot":{"difficulty":"Casual"}}"#;
+        handle_message(&state, "red", request).await;
+        handle_message(&state, "red", r#"{"PlayerLeave":{"id":"red"}}"#).await;
+        skip_messages(&mut [&mut red]);
+        handle_message(&state, "red", request).await;
+        assert!(matches!(
+            red.try_recv(),
+            Ok(WsMsg::Error {
+                code: ErrorCode::BotAlreadyPlaying,
+                ..
+            })
+        ));
+        let lobby = state.room(LOBBY).await.unwrap();
+        assert_eq!(lobby.seats.read().await.iter().flatten().count(), 1);
+        assert!(yellow.try_recv().is_err());
+    }
 }
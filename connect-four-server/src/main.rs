@@ -1,13 +1,18 @@
 use axum::Router;
 use axum::routing::get;
+use connect_four_lib::book::Book;
+use connect_four_lib::bot::Bot;
+use connect_four_lib::config::{Blockers, GameConfig, Rules};
 use connect_four_lib::game::Game;
 use connect_four_lib::player::Player;
-use connect_four_lib::web_socket::WsMsg;
+use connect_four_lib::web_socket::{LOBBY, WsMsg};
 use handlers::ws_handler;
+use rand::Rng;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, RwLock, mpsc};
 use tower_http::services::{ServeDir, ServeFile};
 use tracing::info;
 use tracing_subscriber::FmtSubscriber;
@@ -16,76 +21,416 @@ mod handlers;
 
 #[derive(Debug)]
 struct Connection {
-    id: String,
     tx: mpsc::UnboundedSender<WsMsg>,
+    // Seat of the client on this connection, which decides what it sees in fog of war
+    player: Player,
+}
+
+// Id used in messages about moves made by the computer opponent
+const BOT_ID: &str = "bot";
+
+// Computer opponent and the seat it plays
+struct BotSeat {
+    player: Player,
+    bot: Bot,
+}
+
+// Letters and digits in private room codes, leaving out the ones that are easy to mix up
+// when read out
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+// New private room code like "KQ7-PLM"
+fn room_code(rng: &mut impl Rng) -> String {
+    let mut pick = |len| -> String {
+        (0..len)
+            .map(|_| char::from(CODE_CHARS[rng.gen_range(0..CODE_CHARS.len())]))
+            .collect()
+    };
+    format!("{}-{}", pick(3), pick(3))
 }
 
+// Room id for what a client typed or linked to, so "kq7plm" and "KQ7-PLM" are the same room.
+// Anything that doesn't look like a code is taken as it is.
+fn room_id(text: &str) -> String {
+    let code: String = text
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match code.len() {
+        6 => format!("{}-{}", &code[..3], &code[3..]),
+        _ => text.to_owned(),
+    }
+}
+
+// A client connected to the server, with its own id and the room it is in
+struct Client {
+    tx: mpsc::UnboundedSender<WsMsg>,
+    id: String,
+    room: Option<String>,
+}
+
+// One table: its game, who sits where and every connection watching it. Seats and
+// connections are never locked at the same time.
 #[derive(Clone)]
-struct AppState {
-    player_map: Arc<RwLock<HashMap<String, Player>>>,
-    red_player: Arc<RwLock<Option<String>>>,
-    yellow_player: Arc<RwLock<Option<String>>>,
+struct Room {
+    id: String,
+    // Client id sitting in each seat, indexed by `Player::index`
+    seats: Arc<RwLock<Vec<Option<String>>>>,
     game: Arc<RwLock<Game>>,
     connections: Arc<RwLock<HashMap<String, Connection>>>,
+    bot: Arc<Mutex<Option<BotSeat>>>,
+    // Board size and line length the games in this room are played with
+    config: GameConfig,
+    // Private rooms can ask for a password on top of their code
+    password: Option<String>,
 }
 
-impl AppState {
-    async fn get_player_for_color(&self, color: Player) -> Option<String> {
-        match color {
-            Player::One => self.red_player.read().await.clone(),
-            Player::Two => self.yellow_player.read().await.clone(),
-            _ => None,
+impl Room {
+    fn new(id: String, config: GameConfig) -> Self {
+        Room {
+            id,
+            seats: Arc::new(RwLock::new(vec![None; config.players])),
+            game: Arc::new(RwLock::new(Game::new(config))),
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            bot: Arc::new(Mutex::new(None)),
+            config,
+            password: None,
         }
     }
 
-    async fn set_player_for_color(&self, color: Player, id: Option<String>) {
-        match color {
-            Player::One => {
-                *self.red_player.write().await = id;
-            }
-            Player::Two => {
-                *self.yellow_player.write().await = id;
-            }
-            // TODO: figure out what to do for spectators
-            _ => {}
+    // Sits `id` in the first empty seat in turn order, or makes them a spectator once
+    // everyone is seated
+    async fn take_seat(&self, id: &str) -> Player {
+        let mut seats = self.seats.write().await;
+        let Some(index) = seats.iter().position(Option::is_none) else {
+            return Player::Spectator;
+        };
+        seats[index] = Some(id.to_owned());
+        Player::from_index(index).unwrap_or(Player::Spectator)
+    }
+
+    async fn broadcast(&self, msg: WsMsg) {
+        info!("sending message {:?} in room {}", msg, self.id);
+        let conns_guard = self.connections.read().await;
+        for (_, conn) in conns_guard.iter() {
+            let _ = conn.tx.send(msg.clone());
         }
     }
 
-    async fn leave_player(&mut self, id: String) {
-        let mut red = self.red_player.write().await;
-        let mut yellow = self.yellow_player.write().await;
-        let mut players = self.player_map.write().await;
-        if let Some(player) = &*red {
-            if *player == id {
-                *red = None;
+    // Seat of the client on the connection, which is what it may play as whatever its
+    // messages say
+    async fn player_on(&self, connection_id: &str) -> Player {
+        self.connections
+            .read()
+            .await
+            .get(connection_id)
+            .map_or(Player::Spectator, |conn| conn.player)
+    }
+
+    async fn send_to(&self, connection_id: &str, msg: WsMsg) {
+        info!("sending message {:?} to {}", msg, connection_id);
+        if let Some(conn) = self.connections.read().await.get(connection_id) {
+            let _ = conn.tx.send(msg);
+        }
+    }
+
+    // Sends every connection its own message, built for the player on that connection.
+    // Connections that get `None` are skipped.
+    async fn broadcast_each(&self, msg: impl Fn(Player) -> Option<WsMsg>) {
+        let conns_guard = self.connections.read().await;
+        for (_, conn) in conns_guard.iter() {
+            if let Some(msg) = msg(conn.player) {
+                info!("sending message {:?} to {}", msg, conn.player);
+                let _ = conn.tx.send(msg);
             }
         }
-        if let Some(player) = &*yellow {
-            if *player == id {
-                *yellow = None;
+    }
+
+    // Frees the seat of the client on the connection, which only watches from then on
+    async fn leave_seat(&self, connection_id: &str) {
+        let player = match self.connections.write().await.get_mut(connection_id) {
+            Some(conn) => std::mem::replace(&mut conn.player, Player::Spectator),
+            None => return,
+        };
+        if let Some(index) = player.index()
+            && let Some(seat) = self.seats.write().await.get_mut(index)
+        {
+            *seat = None;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    // Every room with someone in it, by room id
+    rooms: Arc<RwLock<HashMap<String, Room>>>,
+    // Every open connection, by connection id
+    clients: Arc<RwLock<HashMap<String, Client>>>,
+    // Board size and line length new rooms are played with
+    config: GameConfig,
+    // Opening book handed to every perfect bot
+    book: Option<Arc<Book>>,
+}
+
+impl AppState {
+    fn new(config: GameConfig) -> Self {
+        AppState {
+            rooms: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            config,
+            book: None,
+        }
+    }
+
+    fn with_book(self, book: Book) -> Self {
+        AppState {
+            book: Some(Arc::new(book)),
+            ..self
+        }
+    }
+
+    async fn send_to(&self, connection_id: &str, msg: WsMsg) {
+        info!("sending message {:?} to {}", msg, connection_id);
+        if let Some(client) = self.clients.read().await.get(connection_id) {
+            let _ = client.tx.send(msg);
+        }
+    }
+
+    async fn room(&self, id: &str) -> Option<Room> {
+        self.rooms.read().await.get(id).cloned()
+    }
+
+    // Opens a private room under a code no other open room has
+    async fn create_room(&self, password: Option<String>) -> Room {
+        let mut rooms = self.rooms.write().await;
+        let mut rng = rand::thread_rng();
+        let id = loop {
+            let code = room_code(&mut rng);
+            if !rooms.contains_key(&code) {
+                break code;
             }
+        };
+        let room = Room {
+            password,
+            ..Room::new(id.clone(), self.config)
+        };
+        rooms.insert(id, room.clone());
+        room
+    }
+
+    // Room the connection is in, which every game message from it goes to
+    async fn room_of(&self, connection_id: &str) -> Option<Room> {
+        let room = self.clients.read().await.get(connection_id)?.room.clone()?;
+        self.room(&room).await
+    }
+
+    // Moves the connection out of whatever room it is in and into `room`, seated if there is
+    // a free seat. Everyone in the room is told who joined.
+    async fn enter_room(&self, connection_id: &str, room: Room) {
+        self.leave_room(connection_id).await;
+        // Clients racing to open the same room all end up in the one that got registered
+        let room = self
+            .rooms
+            .write()
+            .await
+            .entry(room.id.clone())
+            .or_insert(room)
+            .clone();
+        let client = {
+            let mut clients = self.clients.write().await;
+            clients.get_mut(connection_id).map(|client| {
+                client.room = Some(room.id.clone());
+                (client.tx.clone(), client.id.clone())
+            })
+        };
+        let Some((tx, id)) = client else {
+            self.close_if_empty(&room).await;
+            return;
+        };
+        let player = room.take_seat(&id).await;
+        info!("{} joined room {} as {}", id, room.id, player);
+        room.connections
+            .write()
+            .await
+            .insert(connection_id.to_owned(), Connection { tx, player });
+        // The room may have been closed while it was empty on the way in
+        self.rooms
+            .write()
+            .await
+            .entry(room.id.clone())
+            .or_insert_with(|| room.clone());
+        room.send_to(
+            connection_id,
+            WsMsg::RoomJoined {
+                room: room.id.clone(),
+            },
+        )
+        .await;
+        let game = room.game.read().await;
+        room.broadcast_each(|recipient| {
+            Some(handlers::join_message(&game, &id, player, recipient))
+        })
+        .await;
+    }
+
+    // Takes the connection out of its room, freeing its seat
+    async fn leave_room(&self, connection_id: &str) {
+        let room_id = {
+            let mut clients = self.clients.write().await;
+            let Some(room_id) = clients
+                .get_mut(connection_id)
+                .and_then(|client| client.room.take())
+            else {
+                return;
+            };
+            room_id
+        };
+        let Some(room) = self.room(&room_id).await else {
+            return;
+        };
+        room.leave_seat(connection_id).await;
+        room.connections.write().await.remove(connection_id);
+        self.close_if_empty(&room).await;
+    }
+
+    // Closes the room once the last connection has left, bots don't keep it open. The
+    // registry is never held while waiting on a room, so a room that is busy is left open:
+    // whoever is busy with it either stays in it or checks again on the way out.
+    async fn close_if_empty(&self, room: &Room) {
+        let mut rooms = self.rooms.write().await;
+        let empty = room
+            .connections
+            .try_read()
+            .is_ok_and(|connections| connections.is_empty());
+        let open = rooms
+            .get(&room.id)
+            .is_some_and(|open| Arc::ptr_eq(&open.connections, &room.connections));
+        if empty && open {
+            info!("closing empty room {}", room.id);
+            rooms.remove(&room.id);
         }
-        players.remove(&id);
     }
 }
 
+// Whether `given` is the room's password, taking as long whatever was given so the time
+// doesn't give away how much of it was right
+fn password_matches(password: &str, given: &str) -> bool {
+    let (password, given) = (password.as_bytes(), given.as_bytes());
+    let mut diff = password.len() ^ given.len();
+    for (i, byte) in password.iter().enumerate() {
+        diff |= usize::from(byte ^ given.get(i).copied().unwrap_or(0));
+    }
+    diff == 0
+}
+
+// Reads the number of players from `PLAYERS`, the board size from `BOARD_WIDTH`,
+// `BOARD_HEIGHT` and `CONNECT_LENGTH`, the rules from `GAME_RULES` and whether to play in fog
+// of war or misère from `FOG_OF_WAR` and `MISERE`. Blockers are either listed as
+// `row,col` cells separated by `;` in `BLOCKERS`, or `BLOCKER_COUNT` of them are dropped in at
+// random using `BLOCKER_SEED`. Anything that isn't set comes from the recommended game for that
+// many players.
+fn game_config_from_env() -> Result<GameConfig, Box<dyn std::error::Error>> {
+    let players = match std::env::var("PLAYERS") {
+        Ok(value) => value.parse()?,
+        Err(_) => 2,
+    };
+    let default = GameConfig::for_players(players)?;
+    let var = |name: &str, default: usize| -> Result<usize, Box<dyn std::error::Error>> {
+        match std::env::var(name) {
+            Ok(value) => Ok(value.parse()?),
+            Err(_) => Ok(default),
+        }
+    };
+    let flag = |name: &str| std::env::var(name).is_ok_and(|value| value == "true");
+    let rules = match std::env::var("GAME_RULES").as_deref() {
+        Err(_) | Ok("standard") => Rules::Standard,
+        Ok("popout") => Rules::PopOut,
+        Ok("pop10") => Rules::Pop10,
+        Ok("cylinder") => Rules::Cylinder,
+        Ok(other) => return Err(format!("unknown rules {other:?}").into()),
+    };
+    let width = var("BOARD_WIDTH", default.width)?;
+    let blockers = match (std::env::var("BLOCKERS"), var("BLOCKER_COUNT", 0)?) {
+        (Ok(cells), _) => {
+            let cells = cells
+                .split(';')
+                .map(
+                    |cell| -> Result<(usize, usize), Box<dyn std::error::Error>> {
+                        let (row, col) = cell
+                            .split_once(',')
+                            .ok_or_else(|| format!("blocker {cell:?} isn't row,col"))?;
+                        Ok((row.trim().parse()?, col.trim().parse()?))
+                    },
+                )
+                .collect::<Result<Vec<_>, _>>()?;
+            Blockers::fixed(width, cells)?
+        }
+        (Err(_), 0) => Blockers::None,
+        (Err(_), count) => {
+            // Without a seed every run of the server gets its own layout
+            let seed = match std::env::var("BLOCKER_SEED") {
+                Ok(value) => value.parse()?,
+                Err(_) => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            };
+            Blockers::Random { count, seed }
+        }
+    };
+    Ok(GameConfig::new(
+        width,
+        var("BOARD_HEIGHT", default.height)?,
+        var("CONNECT_LENGTH", default.connect)?,
+    )?
+    .with_rules(rules)
+    .with_blockers(blockers)
+    .with_fog_of_war(flag("FOG_OF_WAR"))
+    .with_misere(flag("MISERE"))
+    .with_players(players)?)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::subscriber::set_global_default(FmtSubscriber::default())?;
 
-    let state = AppState {
-        player_map: Arc::new(RwLock::new(HashMap::new())),
-        red_player: Arc::new(RwLock::new(None)),
-        yellow_player: Arc::new(RwLock::new(None)),
-        game: Arc::new(RwLock::new(Game::default())),
-        connections: Arc::new(RwLock::new(HashMap::new())),
-    };
+    let config = game_config_from_env()?;
+    info!(
+        "{} players playing {}{} rules on a {}x{} board, connect {}{}",
+        config.players,
+        if config.misere { "misère " } else { "" },
+        config.rules,
+        config.width,
+        config.height,
+        config.connect,
+        if config.fog_of_war {
+            ", in fog of war"
+        } else {
+            ""
+        }
+    );
+    if config.blockers != Blockers::None {
+        info!("starting with blockers {:?}", config.blockers);
+    }
+
+    let mut state = AppState::new(config);
+    // Without a book perfect bots only solve positions from the twelfth move on
+    if let Ok(path) = std::env::var("OPENING_BOOK") {
+        let book = Book::load(&path)?;
+        info!(
+            "loaded opening book {} with {} positions up to move {}",
+            path,
+            book.len(),
+            book.depth()
+        );
+        state = state.with_book(book);
+    }
     // let (layer, io) = SocketIo::builder().with_state(state).build_layer();
     // io.ns("/", ws_handler);
 
     let app = Router::new()
         // .route("/", get(ServeFile::new("/dist/index.html")))
         .route("/ws", get(ws_handler))
+        // Links to private rooms open the game, which reads the code from the address
+        .route_service("/r/{code}", ServeFile::new("dist/index.html"))
         .with_state(state)
         .fallback_service(ServeDir::new("dist").precompressed_gzip());
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));